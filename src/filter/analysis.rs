@@ -0,0 +1,196 @@
+use super::*;
+use crate::sampler::{Impulse, Record};
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+#[derive(Clone, Debug)]
+pub struct FrequencyResponse {
+    pub sample_rate: f64,
+    pub frequencies: Vec<f64>,
+    // In dB
+    pub magnitude: Vec<f64>,
+    // Unwrapped, in radians
+    pub phase: Vec<f64>,
+    // In samples
+    pub group_delay: Vec<f64>,
+    pub impulse: Vec<f64>,
+    pub step: Vec<f64>,
+}
+
+impl FrequencyResponse {
+    pub fn analyze<F: Filter>(filter: F, sample_rate: f64, length: usize) -> Self {
+        let mut record = Record::record(
+            Impulse::new(sample_rate),
+            sample_rate,
+            (length as f64 + 0.5) / sample_rate,
+        );
+        record.samples.resize(length, 0.0);
+        record.apply_filter(filter);
+
+        // `Impulse` has an area of one, so its single sample is `sample_rate` high
+        let impulse: Vec<f64> = record.samples.iter().map(|s| s / sample_rate).collect();
+        let step = impulse
+            .iter()
+            .scan(0.0, |sum, s| {
+                *sum += s;
+                Some(*sum)
+            })
+            .collect();
+
+        let spectrum = fft(&impulse);
+        let frequencies = (0..spectrum.len())
+            .map(|k| k as f64 * sample_rate / length as f64)
+            .collect();
+        let magnitude = spectrum
+            .iter()
+            .map(|c| 20.0 * c.norm().max(1e-15).log10())
+            .collect();
+        let mut phase: Vec<f64> = spectrum.iter().map(|c| c.arg()).collect();
+        for k in 1..phase.len() {
+            let jump = phase[k] - phase[k - 1];
+            phase[k] -= 2.0 * PI * (jump / (2.0 * PI)).round();
+        }
+        let step_size = 2.0 * PI / length as f64;
+        let group_delay = (0..phase.len())
+            .map(|k| {
+                let before = k.saturating_sub(1);
+                let after = (k + 1).min(phase.len() - 1);
+                if after == before {
+                    0.0
+                } else {
+                    -(phase[after] - phase[before]) / ((after - before) as f64 * step_size)
+                }
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            frequencies,
+            magnitude,
+            phase,
+            group_delay,
+            impulse,
+            step,
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frequency,magnitude_db,phase_rad,group_delay_samples\n");
+        for k in 0..self.frequencies.len() {
+            writeln!(
+                csv,
+                "{},{},{},{}",
+                self.frequencies[k], self.magnitude[k], self.phase[k], self.group_delay[k]
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    pub fn time_csv(&self) -> String {
+        let mut csv = String::from("time,impulse,step\n");
+        for n in 0..self.impulse.len() {
+            writeln!(
+                csv,
+                "{},{},{}",
+                n as f64 / self.sample_rate,
+                self.impulse[n],
+                self.step[n]
+            )
+            .unwrap();
+        }
+        csv
+    }
+
+    // Magnitude and phase over a logarithmic frequency axis
+    pub fn to_svg(&self) -> String {
+        let (width, height, margin) = (800.0, 300.0, 50.0);
+        let low = self.sample_rate / self.impulse.len() as f64;
+        let high = self.sample_rate / 2.0;
+        let x = |f: f64| margin + (f / low).log10() / (high / low).log10() * (width - 2.0 * margin);
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="10">"#,
+            width,
+            height * 2.0
+        )
+        .unwrap();
+        writeln!(svg, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+
+        let panels = [
+            ("Magnitude (dB)", &self.magnitude, "steelblue"),
+            ("Phase (rad)", &self.phase, "darkorange"),
+        ];
+        for (i, (title, values, color)) in panels.iter().enumerate() {
+            let top = i as f64 * height;
+            let points: Vec<(f64, f64)> = self
+                .frequencies
+                .iter()
+                .zip(values.iter())
+                .filter(|(f, _)| **f >= low)
+                .map(|(f, v)| (*f, *v))
+                .collect();
+            let mut max = points.iter().map(|p| p.1).fold(f64::MIN, f64::max);
+            let mut min = points.iter().map(|p| p.1).fold(f64::MAX, f64::min);
+            if *title == "Magnitude (dB)" {
+                min = min.max(max - 120.0);
+            }
+            if max - min < 1e-9 {
+                max += 1.0;
+                min -= 1.0;
+            }
+            let y =
+                |v: f64| top + margin + (max - v.max(min)) / (max - min) * (height - 2.0 * margin);
+
+            writeln!(
+                svg,
+                r#"<text x="{}" y="{}">{}</text>"#,
+                margin,
+                top + margin - 10.0,
+                title
+            )
+            .unwrap();
+            let mut decade = 10f64.powf(low.log10().ceil());
+            while decade <= high {
+                writeln!(
+                    svg,
+                    r#"<line x1="{0}" y1="{1}" x2="{0}" y2="{2}" stroke="lightgray"/><text x="{0}" y="{3}" text-anchor="middle">{4}</text>"#,
+                    x(decade),
+                    top + margin,
+                    top + height - margin,
+                    top + height - margin + 15.0,
+                    decade
+                )
+                .unwrap();
+                decade *= 10.0;
+            }
+            for v in [min, (min + max) / 2.0, max] {
+                writeln!(
+                    svg,
+                    r#"<line x1="{0}" y1="{1}" x2="{2}" y2="{1}" stroke="lightgray"/><text x="{3}" y="{1}" text-anchor="end">{4:.1}</text>"#,
+                    margin,
+                    y(v),
+                    width - margin,
+                    margin - 5.0,
+                    v
+                )
+                .unwrap();
+            }
+            let polyline: Vec<String> = points
+                .iter()
+                .map(|(f, v)| format!("{:.2},{:.2}", x(*f), y(*v)))
+                .collect();
+            writeln!(
+                svg,
+                r#"<polyline fill="none" stroke="{}" points="{}"/>"#,
+                color,
+                polyline.join(" ")
+            )
+            .unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+}
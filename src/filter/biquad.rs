@@ -0,0 +1,264 @@
+use super::*;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BiquadKind {
+    LowPass,
+    HighPass,
+    BandPass,
+    Notch,
+    AllPass,
+    Peaking,
+    LowShelf,
+    HighShelf,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Coefficients {
+    pub b0: f64,
+    pub b1: f64,
+    pub b2: f64,
+    pub a1: f64,
+    pub a2: f64,
+}
+
+impl Coefficients {
+    pub const IDENTITY: Coefficients = Coefficients {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    // Robert Bristow-Johnson's audio EQ cookbook, `gain` is in dB and only
+    // used by the peaking and shelving kinds.
+    pub fn new(kind: BiquadKind, sample_rate: f64, cutoff: f64, q: f64, gain: f64) -> Self {
+        let cutoff = cutoff.clamp(1.0, sample_rate * 0.49);
+        let q = q.max(1e-3);
+        let w0 = 2.0 * PI * cutoff / sample_rate;
+        let (sin, cos) = w0.sin_cos();
+        let alpha = sin / (2.0 * q);
+        let a = 10f64.powf(gain / 40.0);
+        let sq = 2.0 * a.sqrt() * alpha;
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadKind::LowPass => (
+                (1.0 - cos) / 2.0,
+                1.0 - cos,
+                (1.0 - cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadKind::HighPass => (
+                (1.0 + cos) / 2.0,
+                -(1.0 + cos),
+                (1.0 + cos) / 2.0,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadKind::BandPass => (alpha, 0.0, -alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::Notch => (1.0, -2.0 * cos, 1.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha),
+            BiquadKind::AllPass => (
+                1.0 - alpha,
+                -2.0 * cos,
+                1.0 + alpha,
+                1.0 + alpha,
+                -2.0 * cos,
+                1.0 - alpha,
+            ),
+            BiquadKind::Peaking => (
+                1.0 + alpha * a,
+                -2.0 * cos,
+                1.0 - alpha * a,
+                1.0 + alpha / a,
+                -2.0 * cos,
+                1.0 - alpha / a,
+            ),
+            BiquadKind::LowShelf => (
+                a * ((a + 1.0) - (a - 1.0) * cos + sq),
+                2.0 * a * ((a - 1.0) - (a + 1.0) * cos),
+                a * ((a + 1.0) - (a - 1.0) * cos - sq),
+                (a + 1.0) + (a - 1.0) * cos + sq,
+                -2.0 * ((a - 1.0) + (a + 1.0) * cos),
+                (a + 1.0) + (a - 1.0) * cos - sq,
+            ),
+            BiquadKind::HighShelf => (
+                a * ((a + 1.0) + (a - 1.0) * cos + sq),
+                -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+                a * ((a + 1.0) + (a - 1.0) * cos - sq),
+                (a + 1.0) - (a - 1.0) * cos + sq,
+                2.0 * ((a - 1.0) - (a + 1.0) * cos),
+                (a + 1.0) - (a - 1.0) * cos - sq,
+            ),
+        };
+
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+
+    // Samples until the impulse response decays by 60 dB
+    pub fn tail(&self) -> usize {
+        let discriminant = self.a1 * self.a1 - 4.0 * self.a2;
+        let radius = if discriminant < 0.0 {
+            self.a2.sqrt()
+        } else {
+            let root = discriminant.sqrt();
+            ((-self.a1 + root) / 2.0)
+                .abs()
+                .max(((-self.a1 - root) / 2.0).abs())
+        };
+        if radius >= 1.0 {
+            usize::MAX
+        } else if radius < 1e-9 {
+            2
+        } else {
+            (1e-3f64.ln() / radius.ln()).ceil() as usize + 2
+        }
+    }
+    fn distance(&self, other: &Self) -> f64 {
+        (self.b0 - other.b0)
+            .abs()
+            .max((self.b1 - other.b1).abs())
+            .max((self.b2 - other.b2).abs())
+            .max((self.a1 - other.a1).abs())
+            .max((self.a2 - other.a2).abs())
+    }
+    fn lerp(&self, other: &Self, amount: f64) -> Self {
+        let mix = |a: f64, b: f64| a + (b - a) * amount;
+        Self {
+            b0: mix(self.b0, other.b0),
+            b1: mix(self.b1, other.b1),
+            b2: mix(self.b2, other.b2),
+            a1: mix(self.a1, other.a1),
+            a2: mix(self.a2, other.a2),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Biquad {
+    kind: BiquadKind,
+    sample_rate: f64,
+    cutoff: f64,
+    q: f64,
+    gain: f64,
+    coefficients: Coefficients,
+    target: Coefficients,
+    smoothing: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    pub fn new(kind: BiquadKind, sample_rate: f64, cutoff: f64, q: f64, gain: f64) -> Self {
+        let coefficients = Coefficients::new(kind, sample_rate, cutoff, q, gain);
+        Self {
+            kind,
+            sample_rate,
+            cutoff,
+            q,
+            gain,
+            coefficients,
+            target: coefficients,
+            smoothing: 1.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+    pub fn from_coefficients(coefficients: Coefficients) -> Self {
+        Self {
+            kind: BiquadKind::AllPass,
+            sample_rate: 0.0,
+            cutoff: 0.0,
+            q: 0.0,
+            gain: 0.0,
+            coefficients,
+            target: coefficients,
+            smoothing: 1.0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+    pub fn lowpass(sample_rate: f64, cutoff: f64, q: f64) -> Self {
+        Self::new(BiquadKind::LowPass, sample_rate, cutoff, q, 0.0)
+    }
+    pub fn highpass(sample_rate: f64, cutoff: f64, q: f64) -> Self {
+        Self::new(BiquadKind::HighPass, sample_rate, cutoff, q, 0.0)
+    }
+    pub fn bandpass(sample_rate: f64, cutoff: f64, q: f64) -> Self {
+        Self::new(BiquadKind::BandPass, sample_rate, cutoff, q, 0.0)
+    }
+
+    // Coefficient changes glide towards their new value with a one-pole
+    // smoother of the given time constant. The stability triangle of a
+    // biquad is convex, so every intermediate set of coefficients between
+    // two stable filters is stable too.
+    pub fn set_smoothing(&mut self, time: f64) {
+        self.smoothing = if time > 0.0 && self.sample_rate > 0.0 {
+            1.0 - (-1.0 / (time * self.sample_rate)).exp()
+        } else {
+            1.0
+        };
+    }
+    pub fn set_params(&mut self, cutoff: f64, q: f64, gain: f64) {
+        if self.sample_rate <= 0.0 {
+            return;
+        }
+        self.cutoff = cutoff;
+        self.q = q;
+        self.gain = gain;
+        self.target = Coefficients::new(self.kind, self.sample_rate, cutoff, q, gain);
+        if self.smoothing >= 1.0 {
+            self.coefficients = self.target;
+        }
+    }
+    pub fn set_kind(&mut self, kind: BiquadKind) {
+        self.kind = kind;
+        self.set_params(self.cutoff, self.q, self.gain);
+    }
+    pub fn set_cutoff(&mut self, cutoff: f64) {
+        self.set_params(cutoff, self.q, self.gain);
+    }
+    pub fn set_q(&mut self, q: f64) {
+        self.set_params(self.cutoff, q, self.gain);
+    }
+    pub fn set_gain(&mut self, gain: f64) {
+        self.set_params(self.cutoff, self.q, gain);
+    }
+    pub fn coefficients(&self) -> Coefficients {
+        self.coefficients
+    }
+}
+
+impl Filter for Biquad {
+    fn apply(&mut self, sample: f64) -> f64 {
+        if self.coefficients != self.target {
+            self.coefficients = self.coefficients.lerp(&self.target, self.smoothing);
+            if self.coefficients.distance(&self.target) < 1e-12 {
+                self.coefficients = self.target;
+            }
+        }
+        let c = &self.coefficients;
+        // Transposed direct form II
+        let out = c.b0 * sample + self.z1;
+        self.z1 = c.b1 * sample - c.a1 * out + self.z2;
+        self.z2 = c.b2 * sample - c.a2 * out;
+        out
+    }
+    fn reset(&mut self) {
+        self.coefficients = self.target;
+        self.z1 = 0.0;
+        self.z2 = 0.0;
+    }
+    fn tail(&self) -> usize {
+        self.target.tail()
+    }
+}
@@ -0,0 +1,120 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct Series {
+    pub filters: Vec<DynFilter>,
+}
+
+impl Series {
+    pub fn new(filters: Vec<DynFilter>) -> Self {
+        Self { filters }
+    }
+}
+
+impl Filter for Series {
+    fn apply(&mut self, sample: f64) -> f64 {
+        self.filters
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.apply(sample))
+    }
+    fn reset(&mut self) {
+        self.filters.iter_mut().for_each(|f| f.reset());
+    }
+    fn process(&mut self, samples: &mut [f64]) {
+        self.filters.iter_mut().for_each(|f| f.process(samples));
+    }
+    fn latency(&self) -> usize {
+        self.filters.iter().map(|f| f.latency()).sum()
+    }
+    fn tail(&self) -> usize {
+        self.filters
+            .iter()
+            .fold(0usize, |tail, f| tail.saturating_add(f.tail()))
+    }
+}
+
+// Branches are delayed to line up with the slowest one before summing
+#[derive(Clone)]
+pub struct Parallel {
+    branches: Vec<(f64, DynFilter, DelayLine)>,
+}
+
+impl Parallel {
+    pub fn new(filters: Vec<(f64, DynFilter)>) -> Self {
+        let latency = filters.iter().map(|(_, f)| f.latency()).max().unwrap_or(0);
+        Self {
+            branches: filters
+                .into_iter()
+                .map(|(gain, f)| {
+                    let compensation = DelayLine::new(latency - f.latency());
+                    (gain, f, compensation)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Filter for Parallel {
+    fn apply(&mut self, sample: f64) -> f64 {
+        self.branches
+            .iter_mut()
+            .map(|(gain, filter, compensation)| compensation.apply(filter.apply(sample)) * *gain)
+            .sum()
+    }
+    fn reset(&mut self) {
+        for (_, filter, compensation) in self.branches.iter_mut() {
+            filter.reset();
+            compensation.reset();
+        }
+    }
+    fn latency(&self) -> usize {
+        self.branches
+            .iter()
+            .map(|(_, f, c)| f.latency() + c.len())
+            .max()
+            .unwrap_or(0)
+    }
+    fn tail(&self) -> usize {
+        self.branches
+            .iter()
+            .map(|(_, f, c)| f.tail().saturating_add(c.len()))
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// Wet/dry blend, the dry signal is delayed by the latency of the filter
+#[derive(Clone)]
+pub struct Mix {
+    pub wet: f64,
+    filter: DynFilter,
+    dry: DelayLine,
+}
+
+impl Mix {
+    pub fn new(filter: DynFilter, wet: f64) -> Self {
+        Self {
+            wet,
+            dry: DelayLine::new(filter.latency()),
+            filter,
+        }
+    }
+}
+
+impl Filter for Mix {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let wet = self.filter.apply(sample);
+        let dry = self.dry.apply(sample);
+        wet * self.wet + dry * (1.0 - self.wet)
+    }
+    fn reset(&mut self) {
+        self.filter.reset();
+        self.dry.reset();
+    }
+    fn latency(&self) -> usize {
+        self.filter.latency()
+    }
+    fn tail(&self) -> usize {
+        self.filter.tail()
+    }
+}
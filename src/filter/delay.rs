@@ -0,0 +1,85 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct DelayLine {
+    buffer: Vec<f64>,
+    position: usize,
+}
+
+impl DelayLine {
+    pub fn new(length: usize) -> Self {
+        Self {
+            buffer: vec![0.0; length],
+            position: 0,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+    pub fn write(&mut self, sample: f64) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        self.buffer[self.position] = sample;
+        self.position = (self.position + 1) % self.buffer.len();
+    }
+    // Reads `delay` samples behind the last written one, `delay` may be
+    // fractional and is clamped to the length of the line
+    pub fn read(&self, delay: f64, interpolation: Interpolation) -> f64 {
+        let len = self.buffer.len();
+        if len == 0 {
+            return 0.0;
+        }
+        let delay = delay.clamp(0.0, (len - 1) as f64);
+        let whole = delay.floor() as usize;
+        let fraction = delay - delay.floor();
+        let at = |offset: isize| {
+            let offset = (whole as isize + offset).clamp(0, len as isize - 1);
+            let index = self.position as isize - 1 - offset;
+            self.buffer[index.rem_euclid(len as isize) as usize]
+        };
+        match interpolation {
+            Interpolation::Nearest => at(fraction.round() as isize),
+            Interpolation::Linear => at(0) + (at(1) - at(0)) * fraction,
+            Interpolation::Cubic => {
+                // Catmull-Rom through the four surrounding samples
+                let (y0, y1, y2, y3) = (at(-1), at(0), at(1), at(2));
+                let c1 = 0.5 * (y2 - y0);
+                let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+                let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+                ((c3 * fraction + c2) * fraction + c1) * fraction + y1
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Nearest,
+    Linear,
+    Cubic,
+}
+
+impl Filter for DelayLine {
+    fn apply(&mut self, sample: f64) -> f64 {
+        if self.buffer.is_empty() {
+            return sample;
+        }
+        let out = std::mem::replace(&mut self.buffer[self.position], sample);
+        self.position = (self.position + 1) % self.buffer.len();
+        out
+    }
+    fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|s| *s = 0.0);
+        self.position = 0;
+    }
+    fn latency(&self) -> usize {
+        self.buffer.len()
+    }
+    fn tail(&self) -> usize {
+        self.buffer.len()
+    }
+}
@@ -0,0 +1,96 @@
+use super::*;
+use crate::sampler::DynSampler;
+
+// Runs a transfer curve at `factor` times the sample rate, band limiting
+// before and after so harmonics created by the curve don't alias.
+#[derive(Clone)]
+pub struct Waveshaper {
+    curve: DynSampler,
+    factor: usize,
+    upsample: Fir,
+    downsample: Fir,
+}
+
+impl Waveshaper {
+    pub fn new(curve: DynSampler, factor: usize) -> Self {
+        let factor = factor.max(1);
+        let taps = 16 * factor + 1;
+        let band_limit = || {
+            Fir::lowpass(
+                factor as f64,
+                0.45,
+                if factor > 1 { taps } else { 1 },
+                WindowFunction::Kaiser(8.0),
+            )
+        };
+        Self {
+            curve,
+            factor,
+            upsample: band_limit(),
+            downsample: band_limit(),
+        }
+    }
+}
+
+impl Filter for Waveshaper {
+    fn apply(&mut self, sample: f64) -> f64 {
+        if self.factor == 1 {
+            return self.curve.sample(sample);
+        }
+        let mut out = 0.0;
+        for i in 0..self.factor {
+            // Zero stuffing, the gain makes up for the inserted zeros
+            let stuffed = if i == 0 {
+                sample * self.factor as f64
+            } else {
+                0.0
+            };
+            let shaped = self.curve.sample(self.upsample.apply(stuffed));
+            out = self.downsample.apply(shaped);
+        }
+        out
+    }
+    fn reset(&mut self) {
+        self.upsample.reset();
+        self.downsample.reset();
+    }
+    fn latency(&self) -> usize {
+        (self.upsample.latency() + self.downsample.latency()) / self.factor
+    }
+    fn tail(&self) -> usize {
+        (self.upsample.tail() + self.downsample.tail()) / self.factor
+    }
+}
+
+// Sample rate reduction by holding every `ratio`-th sample
+#[derive(Clone)]
+pub struct RateReducer {
+    ratio: f64,
+    phase: f64,
+    held: f64,
+}
+
+impl RateReducer {
+    pub fn new(sample_rate: f64, target_rate: f64) -> Self {
+        Self {
+            ratio: (target_rate / sample_rate).min(1.0),
+            phase: 1.0,
+            held: 0.0,
+        }
+    }
+}
+
+impl Filter for RateReducer {
+    fn apply(&mut self, sample: f64) -> f64 {
+        if self.phase >= 1.0 {
+            self.phase -= 1.0;
+            self.held = sample;
+        }
+        self.phase += self.ratio;
+        self.held
+    }
+    fn reset(&mut self) {
+        self.phase = 1.0;
+        self.held = 0.0;
+    }
+}
@@ -0,0 +1,357 @@
+use super::*;
+use crate::sampler::DynSampler;
+use std::collections::VecDeque;
+
+fn to_db(amplitude: f64) -> f64 {
+    20.0 * amplitude.abs().max(1e-12).log10()
+}
+
+fn from_db(db: f64) -> f64 {
+    10f64.powf(db / 20.0)
+}
+
+fn coefficient(time: f64, sample_rate: f64) -> f64 {
+    if time > 0.0 {
+        (-1.0 / (time * sample_rate)).exp()
+    } else {
+        0.0
+    }
+}
+
+// A key signal that runs alongside the filtered one, e.g. the kick channel
+// of a song ducking its bass channel
+#[derive(Clone)]
+struct Sidechain {
+    key: Option<DynSampler>,
+    sample_rate: f64,
+    position: usize,
+}
+
+impl Sidechain {
+    fn next(&mut self, sample: f64) -> f64 {
+        let key = match &self.key {
+            Some(key) => key.sample(self.position as f64 / self.sample_rate),
+            None => sample,
+        };
+        self.position += 1;
+        key
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DynamicsKind {
+    Compressor,
+    Expander,
+    Gate,
+}
+
+// Feed-forward dynamics processor, all levels are in dB and times in seconds
+#[derive(Clone)]
+pub struct Dynamics {
+    pub kind: DynamicsKind,
+    pub threshold: f64,
+    pub ratio: f64,
+    pub knee: f64,
+    pub makeup: f64,
+    // Maximum gain reduction
+    pub range: f64,
+    attack: f64,
+    release: f64,
+    sidechain: Sidechain,
+    reduction: f64,
+}
+
+impl Dynamics {
+    pub fn new(
+        kind: DynamicsKind,
+        sample_rate: f64,
+        threshold: f64,
+        ratio: f64,
+        knee: f64,
+        attack: f64,
+        release: f64,
+    ) -> Self {
+        Self {
+            kind,
+            threshold,
+            ratio: ratio.max(1.0),
+            knee: knee.max(0.0),
+            makeup: 0.0,
+            range: 120.0,
+            attack: coefficient(attack, sample_rate),
+            release: coefficient(release, sample_rate),
+            sidechain: Sidechain {
+                key: None,
+                sample_rate,
+                position: 0,
+            },
+            reduction: 0.0,
+        }
+    }
+    pub fn compressor(sample_rate: f64, threshold: f64, ratio: f64) -> Self {
+        Self::new(
+            DynamicsKind::Compressor,
+            sample_rate,
+            threshold,
+            ratio,
+            6.0,
+            0.01,
+            0.1,
+        )
+    }
+    pub fn expander(sample_rate: f64, threshold: f64, ratio: f64) -> Self {
+        Self::new(
+            DynamicsKind::Expander,
+            sample_rate,
+            threshold,
+            ratio,
+            6.0,
+            0.005,
+            0.1,
+        )
+    }
+    pub fn gate(sample_rate: f64, threshold: f64) -> Self {
+        let mut gate = Self::new(
+            DynamicsKind::Gate,
+            sample_rate,
+            threshold,
+            100.0,
+            0.0,
+            0.001,
+            0.05,
+        );
+        gate.range = 80.0;
+        gate
+    }
+    pub fn set_attack(&mut self, attack: f64) {
+        self.attack = coefficient(attack, self.sidechain.sample_rate);
+    }
+    pub fn set_release(&mut self, release: f64) {
+        self.release = coefficient(release, self.sidechain.sample_rate);
+    }
+    pub fn set_sidechain(&mut self, key: DynSampler) {
+        self.sidechain.key = Some(key);
+    }
+    // Current gain reduction in dB, for metering
+    pub fn gain_reduction(&self) -> f64 {
+        self.reduction
+    }
+
+    // Static curve, maps an input level to an output level
+    pub fn curve(&self, level: f64) -> f64 {
+        let (t, w, r) = (self.threshold, self.knee, self.ratio);
+        let over = level - t;
+        let output = match self.kind {
+            DynamicsKind::Compressor => {
+                if 2.0 * over < -w {
+                    level
+                } else if w > 0.0 && 2.0 * over <= w {
+                    level + (1.0 / r - 1.0) * (over + w / 2.0).powi(2) / (2.0 * w)
+                } else {
+                    t + over / r
+                }
+            }
+            DynamicsKind::Expander | DynamicsKind::Gate => {
+                if 2.0 * over > w {
+                    level
+                } else if w > 0.0 && 2.0 * over >= -w {
+                    level - (r - 1.0) * (over - w / 2.0).powi(2) / (2.0 * w)
+                } else {
+                    t + over * r
+                }
+            }
+        };
+        output.max(level - self.range)
+    }
+
+    pub fn apply_keyed(&mut self, sample: f64, key: f64) -> f64 {
+        let level = to_db(key);
+        let target = level - self.curve(level);
+        // Attack is how fast a compressor clamps down and how fast an
+        // expander opens up
+        let attacking = match self.kind {
+            DynamicsKind::Compressor => target > self.reduction,
+            _ => target < self.reduction,
+        };
+        let c = if attacking { self.attack } else { self.release };
+        self.reduction = c * self.reduction + (1.0 - c) * target;
+        sample * from_db(self.makeup - self.reduction)
+    }
+}
+
+impl Filter for Dynamics {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let key = self.sidechain.next(sample);
+        self.apply_keyed(sample, key)
+    }
+    fn reset(&mut self) {
+        self.reduction = 0.0;
+        self.sidechain.position = 0;
+    }
+}
+
+// Brickwall look-ahead limiter. The required gain is held for the length
+// of the look-ahead and then averaged over the same length, so the gain
+// has fully ramped down by the time a peak leaves the delay line.
+#[derive(Clone)]
+pub struct Limiter {
+    pub ceiling: f64,
+    // Level where limiting starts, the ceiling unless lowered to compress
+    // softly below it
+    pub threshold: f64,
+    pub ratio: f64,
+    pub knee: f64,
+    pub makeup: f64,
+    attack: f64,
+    release: f64,
+    length: usize,
+    delay: DelayLine,
+    hold: VecDeque<(usize, f64)>,
+    average: VecDeque<f64>,
+    sum: f64,
+    gain: f64,
+    position: usize,
+    sidechain: Sidechain,
+}
+
+impl Limiter {
+    pub fn new(sample_rate: f64, ceiling: f64, lookahead: f64, release: f64, makeup: f64) -> Self {
+        let length = ((lookahead * sample_rate) as usize).max(1);
+        Self {
+            ceiling,
+            threshold: ceiling,
+            ratio: f64::INFINITY,
+            knee: 0.0,
+            makeup,
+            attack: 0.0,
+            release: coefficient(release, sample_rate),
+            length,
+            delay: DelayLine::new(length - 1),
+            hold: VecDeque::new(),
+            average: VecDeque::new(),
+            sum: 0.0,
+            gain: 1.0,
+            position: 0,
+            sidechain: Sidechain {
+                key: None,
+                sample_rate,
+                position: 0,
+            },
+        }
+    }
+    // Zero, the default, follows the look-ahead ramp exactly. Longer
+    // attacks let transients through to the final clip at the ceiling.
+    pub fn set_attack(&mut self, attack: f64) {
+        self.attack = coefficient(attack, self.sidechain.sample_rate);
+    }
+    pub fn set_release(&mut self, release: f64) {
+        self.release = coefficient(release, self.sidechain.sample_rate);
+    }
+    pub fn set_sidechain(&mut self, key: DynSampler) {
+        self.sidechain.key = Some(key);
+    }
+    pub fn gain_reduction(&self) -> f64 {
+        -to_db(self.gain)
+    }
+
+    // Static curve like a compressor's, never above the ceiling
+    pub fn curve(&self, level: f64) -> f64 {
+        let (t, w, r) = (self.threshold, self.knee, self.ratio.max(1.0));
+        let over = level - t;
+        let output = if 2.0 * over < -w {
+            level
+        } else if w > 0.0 && 2.0 * over <= w {
+            level + (1.0 / r - 1.0) * (over + w / 2.0).powi(2) / (2.0 * w)
+        } else {
+            t + over / r
+        };
+        output.min(self.ceiling)
+    }
+
+    pub fn apply_keyed(&mut self, sample: f64, key: f64) -> f64 {
+        let makeup = from_db(self.makeup);
+        let ceiling = from_db(self.ceiling);
+        let level = to_db(key * makeup);
+        let required = from_db(self.curve(level) - level).min(1.0);
+
+        // Sliding minimum over the look-ahead window
+        while matches!(self.hold.back(), Some((_, g)) if *g >= required) {
+            self.hold.pop_back();
+        }
+        self.hold.push_back((self.position, required));
+        while matches!(self.hold.front(), Some((i, _)) if i + self.length <= self.position) {
+            self.hold.pop_front();
+        }
+        self.position += 1;
+        let held = self.hold.front().unwrap().1;
+
+        self.average.push_back(held);
+        self.sum += held;
+        if self.average.len() > self.length {
+            self.sum -= self.average.pop_front().unwrap();
+        }
+        let smoothed = self.sum / self.length as f64
+            + (self.length - self.average.len()) as f64 / self.length as f64;
+
+        self.gain = if smoothed < self.gain {
+            self.attack * self.gain + (1.0 - self.attack) * smoothed
+        } else {
+            self.release * self.gain + (1.0 - self.release) * smoothed
+        };
+        let out = self.delay.apply(sample) * makeup * self.gain;
+        out.clamp(-ceiling, ceiling)
+    }
+}
+
+impl Filter for Limiter {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let key = self.sidechain.next(sample);
+        self.apply_keyed(sample, key)
+    }
+    fn reset(&mut self) {
+        self.delay.reset();
+        self.hold.clear();
+        self.average.clear();
+        self.sum = 0.0;
+        self.gain = 1.0;
+        self.position = 0;
+        self.sidechain.position = 0;
+    }
+    fn latency(&self) -> usize {
+        self.length - 1
+    }
+    fn tail(&self) -> usize {
+        self.length - 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 44100.0;
+
+    #[test]
+    fn hard_knees_pass_levels_at_the_threshold() {
+        let mut compressor = Dynamics::new(
+            DynamicsKind::Compressor,
+            SAMPLE_RATE,
+            0.0,
+            4.0,
+            0.0,
+            0.01,
+            0.1,
+        );
+        assert_eq!(compressor.curve(0.0), 0.0);
+        let output = (0..SAMPLE_RATE as usize)
+            .map(|_| compressor.apply(1.0))
+            .last()
+            .unwrap();
+        assert!((output - 1.0).abs() < 1e-9);
+
+        let gate = Dynamics::gate(SAMPLE_RATE, -40.0);
+        assert_eq!(gate.curve(-40.0), -40.0);
+        // 100:1 below the threshold, down to the 80 dB range
+        assert_eq!(gate.curve(-41.0), -41.0 - 80.0);
+    }
+}
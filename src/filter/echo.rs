@@ -0,0 +1,175 @@
+use super::*;
+use crate::sampler::Record;
+use crate::tempo::Length;
+
+fn feedback_tail(feedback: f64, length: usize) -> usize {
+    if feedback.abs() < 1e-6 {
+        length
+    } else if feedback.abs() >= 1.0 {
+        usize::MAX
+    } else {
+        (1e-3f64.ln() / feedback.abs().ln()).ceil() as usize * length
+    }
+}
+
+// Feedback echo, an optional filter in the loop darkens or thins out every
+// repeat
+#[derive(Clone)]
+pub struct Echo {
+    pub feedback: f64,
+    pub mix: f64,
+    delay: f64,
+    line: DelayLine,
+    feedback_filter: Option<DynFilter>,
+}
+
+impl Echo {
+    pub fn new(sample_rate: f64, delay: Length, feedback: f64, mix: f64) -> Self {
+        let delay = delay.seconds() * sample_rate;
+        Self {
+            feedback,
+            mix,
+            delay,
+            line: DelayLine::new(delay.ceil() as usize + 2),
+            feedback_filter: None,
+        }
+    }
+    pub fn set_feedback_filter(&mut self, filter: DynFilter) {
+        self.feedback_filter = Some(filter);
+    }
+}
+
+impl Filter for Echo {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let delayed = self.line.read(self.delay - 1.0, Interpolation::Linear);
+        let looped = match &mut self.feedback_filter {
+            Some(filter) => filter.apply(delayed),
+            None => delayed,
+        };
+        self.line.write(sample + looped * self.feedback);
+        delayed * self.mix + sample * (1.0 - self.mix)
+    }
+    fn reset(&mut self) {
+        self.line.reset();
+        if let Some(filter) = &mut self.feedback_filter {
+            filter.reset();
+        }
+    }
+    fn tail(&self) -> usize {
+        feedback_tail(self.feedback, self.line.len())
+    }
+}
+
+// Repeats bounce between the left and the right channel
+#[derive(Clone)]
+pub struct PingPong {
+    pub feedback: f64,
+    pub mix: f64,
+    delay: f64,
+    left: DelayLine,
+    right: DelayLine,
+}
+
+impl PingPong {
+    pub fn new(sample_rate: f64, delay: Length, feedback: f64, mix: f64) -> Self {
+        let delay = delay.seconds() * sample_rate;
+        let length = delay.ceil() as usize + 2;
+        Self {
+            feedback,
+            mix,
+            delay,
+            left: DelayLine::new(length),
+            right: DelayLine::new(length),
+        }
+    }
+    pub fn apply_stereo(&mut self, left: f64, right: f64) -> (f64, f64) {
+        let l = self.left.read(self.delay - 1.0, Interpolation::Linear);
+        let r = self.right.read(self.delay - 1.0, Interpolation::Linear);
+        // Both inputs enter on the left, the right line is only fed by it
+        self.left.write((left + right) / 2.0 + r * self.feedback);
+        self.right.write(l);
+        (
+            l * self.mix + left * (1.0 - self.mix),
+            r * self.mix + right * (1.0 - self.mix),
+        )
+    }
+    pub fn process_stereo(&mut self, record: &Record) -> (Record, Record) {
+        let (left, right) = record
+            .samples
+            .iter()
+            .map(|s| self.apply_stereo(*s, *s))
+            .unzip();
+        (
+            Record {
+                sample_rate: record.sample_rate,
+                samples: left,
+            },
+            Record {
+                sample_rate: record.sample_rate,
+                samples: right,
+            },
+        )
+    }
+}
+
+impl Filter for PingPong {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let (left, right) = self.apply_stereo(sample, sample);
+        (left + right) / 2.0
+    }
+    fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+    }
+    fn tail(&self) -> usize {
+        feedback_tail(self.feedback, self.left.len()).saturating_mul(2)
+    }
+}
+
+// Several taps of a single delay line, each with its own gain. The longest
+// tap is fed back into the line.
+#[derive(Clone)]
+pub struct MultiTap {
+    pub feedback: f64,
+    pub mix: f64,
+    taps: Vec<(f64, f64)>,
+    line: DelayLine,
+}
+
+impl MultiTap {
+    pub fn new(sample_rate: f64, taps: Vec<(Length, f64)>, feedback: f64, mix: f64) -> Self {
+        let taps: Vec<(f64, f64)> = taps
+            .into_iter()
+            .map(|(delay, gain)| (delay.seconds() * sample_rate, gain))
+            .collect();
+        let longest = taps.iter().map(|t| t.0).fold(0.0, f64::max);
+        Self {
+            feedback,
+            mix,
+            taps,
+            line: DelayLine::new(longest.ceil() as usize + 2),
+        }
+    }
+}
+
+impl Filter for MultiTap {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let mut wet = 0.0;
+        let mut longest = (0.0, 0.0);
+        for (delay, gain) in self.taps.iter() {
+            let tap = self.line.read(delay - 1.0, Interpolation::Linear);
+            wet += tap * gain;
+            if *delay >= longest.0 {
+                longest = (*delay, tap);
+            }
+        }
+        self.line.write(sample + longest.1 * self.feedback);
+        wet * self.mix + sample * (1.0 - self.mix)
+    }
+    fn reset(&mut self) {
+        self.line.reset();
+    }
+    fn tail(&self) -> usize {
+        feedback_tail(self.feedback, self.line.len())
+    }
+}
@@ -0,0 +1,399 @@
+use super::*;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WindowFunction {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+    Kaiser(f64),
+}
+
+impl WindowFunction {
+    pub fn coefficients(&self, length: usize) -> Vec<f64> {
+        let m = (length.max(2) - 1) as f64;
+        (0..length)
+            .map(|n| {
+                let x = n as f64 / m;
+                match self {
+                    WindowFunction::Rectangular => 1.0,
+                    WindowFunction::Hann => 0.5 - 0.5 * (2.0 * PI * x).cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * (2.0 * PI * x).cos(),
+                    WindowFunction::Blackman => {
+                        0.42 - 0.5 * (2.0 * PI * x).cos() + 0.08 * (4.0 * PI * x).cos()
+                    }
+                    WindowFunction::Kaiser(beta) => {
+                        let r = 2.0 * x - 1.0;
+                        bessel_i0(beta * (1.0 - r * r).sqrt()) / bessel_i0(*beta)
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let mut k = 1.0;
+    while term > sum * 1e-16 {
+        term *= (x / (2.0 * k)).powi(2);
+        sum += term;
+        k += 1.0;
+    }
+    sum
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[derive(Clone)]
+pub struct Fir {
+    taps: Vec<f64>,
+    reversed: Vec<f64>,
+    // Every sample is written twice so the newest `taps.len()` samples are
+    // always contiguous
+    buffer: Vec<f64>,
+    position: usize,
+}
+
+impl Fir {
+    pub fn new(taps: Vec<f64>) -> Self {
+        let reversed = taps.iter().rev().cloned().collect();
+        Self {
+            buffer: vec![0.0; taps.len() * 2],
+            reversed,
+            taps,
+            position: 0,
+        }
+    }
+    pub fn taps(&self) -> &[f64] {
+        &self.taps
+    }
+
+    pub fn lowpass(sample_rate: f64, cutoff: f64, length: usize, window: WindowFunction) -> Self {
+        Self::new(windowed_sinc(sample_rate, cutoff, length, window))
+    }
+    pub fn highpass(sample_rate: f64, cutoff: f64, length: usize, window: WindowFunction) -> Self {
+        Self::new(invert(windowed_sinc(sample_rate, cutoff, length, window)))
+    }
+    pub fn bandpass(
+        sample_rate: f64,
+        low: f64,
+        high: f64,
+        length: usize,
+        window: WindowFunction,
+    ) -> Self {
+        let upper = windowed_sinc(sample_rate, high, length, window);
+        let lower = windowed_sinc(sample_rate, low, length, window);
+        Self::new(upper.iter().zip(lower.iter()).map(|(u, l)| u - l).collect())
+    }
+    pub fn bandstop(
+        sample_rate: f64,
+        low: f64,
+        high: f64,
+        length: usize,
+        window: WindowFunction,
+    ) -> Self {
+        Self::new(invert(
+            Self::bandpass(sample_rate, low, high, length, window).taps,
+        ))
+    }
+
+    // Parks-McClellan equiripple design of an odd length linear phase
+    // filter. `bands` are `(start, end)` frequencies in Hz, each with a
+    // desired gain and an error weight.
+    pub fn equiripple(
+        sample_rate: f64,
+        length: usize,
+        bands: &[(f64, f64)],
+        desired: &[f64],
+        weights: &[f64],
+    ) -> Self {
+        assert!(!length.is_multiple_of(2), "Only odd lengths are supported!");
+        Self::new(remez(
+            length,
+            &bands
+                .iter()
+                .map(|(a, b)| (2.0 * PI * a / sample_rate, 2.0 * PI * b / sample_rate))
+                .collect::<Vec<_>>(),
+            desired,
+            weights,
+        ))
+    }
+}
+
+impl Filter for Fir {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let n = self.taps.len();
+        if n == 0 {
+            return 0.0;
+        }
+        self.buffer[self.position] = sample;
+        self.buffer[self.position + n] = sample;
+        let window = &self.buffer[self.position + 1..self.position + 1 + n];
+        self.position = (self.position + 1) % n;
+        window
+            .iter()
+            .zip(self.reversed.iter())
+            .map(|(x, h)| x * h)
+            .sum()
+    }
+    fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|s| *s = 0.0);
+        self.position = 0;
+    }
+    // Only linear phase designs have a well defined delay
+    fn latency(&self) -> usize {
+        let n = self.taps.len();
+        let symmetric = (0..n / 2).all(|i| (self.taps[i] - self.taps[n - 1 - i]).abs() < 1e-12);
+        if symmetric {
+            n.saturating_sub(1) / 2
+        } else {
+            0
+        }
+    }
+    fn tail(&self) -> usize {
+        self.taps.len().saturating_sub(1)
+    }
+}
+
+fn windowed_sinc(sample_rate: f64, cutoff: f64, length: usize, window: WindowFunction) -> Vec<f64> {
+    let fc = cutoff / sample_rate;
+    let middle = (length as f64 - 1.0) / 2.0;
+    let taps: Vec<f64> = window
+        .coefficients(length)
+        .into_iter()
+        .enumerate()
+        .map(|(n, w)| 2.0 * fc * sinc(2.0 * fc * (n as f64 - middle)) * w)
+        .collect();
+    let sum: f64 = taps.iter().sum();
+    taps.into_iter().map(|h| h / sum).collect()
+}
+
+// Spectral inversion, only valid for odd lengths
+fn invert(mut taps: Vec<f64>) -> Vec<f64> {
+    assert!(
+        !taps.len().is_multiple_of(2),
+        "Only odd lengths are supported!"
+    );
+    taps.iter_mut().for_each(|h| *h = -*h);
+    let middle = taps.len() / 2;
+    taps[middle] += 1.0;
+    taps
+}
+
+fn remez(length: usize, bands: &[(f64, f64)], desired: &[f64], weights: &[f64]) -> Vec<f64> {
+    let half = (length - 1) / 2;
+    let extremals = half + 2;
+
+    // Dense grid over the bands, `edges` marks the first point of each band
+    let total: f64 = bands.iter().map(|(a, b)| b - a).sum();
+    let density = 16 * extremals;
+    let mut grid = Vec::new();
+    let mut band_of = Vec::new();
+    for (i, (a, b)) in bands.iter().enumerate() {
+        let points = ((b - a) / total * density as f64).ceil().max(2.0) as usize;
+        for k in 0..points {
+            grid.push(a + (b - a) * k as f64 / (points - 1) as f64);
+            band_of.push(i);
+        }
+    }
+    let target: Vec<f64> = band_of.iter().map(|&b| desired[b]).collect();
+    let weight: Vec<f64> = band_of.iter().map(|&b| weights[b]).collect();
+
+    let mut indices: Vec<usize> = (0..extremals)
+        .map(|k| k * (grid.len() - 1) / (extremals - 1))
+        .collect();
+    let interpolate = |indices: &[usize]| {
+        let x: Vec<f64> = indices.iter().map(|&i| grid[i].cos()).collect();
+        let weights = |points: &[f64]| -> Vec<f64> {
+            (0..points.len())
+                .map(|i| {
+                    1.0 / (0..points.len())
+                        .filter(|&j| j != i)
+                        .map(|j| points[i] - points[j])
+                        .product::<f64>()
+                })
+                .collect()
+        };
+        let b = weights(&x);
+        let mut numerator = 0.0;
+        let mut denominator = 0.0;
+        for (k, &i) in indices.iter().enumerate() {
+            let sign = if k.is_multiple_of(2) { 1.0 } else { -1.0 };
+            numerator += b[k] * target[i];
+            denominator += b[k] * sign / weight[i];
+        }
+        let delta = numerator / denominator;
+        let points = &x[..x.len() - 1];
+        let values: Vec<f64> = indices[..indices.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(k, &i)| {
+                let sign = if k.is_multiple_of(2) { 1.0 } else { -1.0 };
+                target[i] - sign * delta / weight[i]
+            })
+            .collect();
+        let b = weights(points);
+        let points = points.to_vec();
+        let amplitude = move |w: f64| {
+            let x = w.cos();
+            let mut numerator = 0.0;
+            let mut denominator = 0.0;
+            for k in 0..points.len() {
+                let d = x - points[k];
+                if d.abs() < 1e-13 {
+                    return values[k];
+                }
+                numerator += b[k] / d * values[k];
+                denominator += b[k] / d;
+            }
+            numerator / denominator
+        };
+        (delta, amplitude)
+    };
+
+    for _ in 0..64 {
+        let (delta, amplitude) = interpolate(&indices);
+        let error: Vec<f64> = (0..grid.len())
+            .map(|i| weight[i] * (target[i] - amplitude(grid[i])))
+            .collect();
+
+        let mut candidates: Vec<usize> = (0..grid.len())
+            .filter(|&i| {
+                let e = error[i];
+                let left = i > 0 && band_of[i - 1] == band_of[i];
+                let right = i + 1 < grid.len() && band_of[i + 1] == band_of[i];
+                // Signed, a small error of the other sign next to a
+                // reference point doesn't hide it
+                let s = e.signum();
+                let peak =
+                    (!left || s * e >= s * error[i - 1]) && (!right || s * e >= s * error[i + 1]);
+                peak && e.abs() >= delta.abs() * 0.999
+            })
+            .collect();
+        let mut alternating: Vec<usize> = Vec::new();
+        for i in candidates.drain(..) {
+            match alternating.last() {
+                Some(&last) if error[last].signum() == error[i].signum() => {
+                    if error[i].abs() > error[last].abs() {
+                        *alternating.last_mut().unwrap() = i;
+                    }
+                }
+                _ => alternating.push(i),
+            }
+        }
+        while alternating.len() > extremals {
+            if error[alternating[0]].abs() < error[*alternating.last().unwrap()].abs() {
+                alternating.remove(0);
+            } else {
+                alternating.pop();
+            }
+        }
+        if alternating.len() < extremals {
+            break;
+        }
+        let maximum = alternating
+            .iter()
+            .map(|&i| error[i].abs())
+            .fold(0.0, f64::max);
+        let converged = alternating == indices || maximum - delta.abs() < 1e-9 * maximum;
+        indices = alternating;
+        if converged {
+            break;
+        }
+    }
+
+    // Frequency sampling of the final amplitude response
+    let (_, amplitude) = interpolate(&indices);
+    let n = length as f64;
+    let samples: Vec<f64> = (0..=half)
+        .map(|k| amplitude(2.0 * PI * k as f64 / n))
+        .collect();
+    (0..length)
+        .map(|i| {
+            let offset = i as f64 - half as f64;
+            (samples[0]
+                + 2.0
+                    * (1..=half)
+                        .map(|k| samples[k] * (2.0 * PI * k as f64 * offset / n).cos())
+                        .sum::<f64>())
+                / n
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn gain(fir: &Fir, frequency: f64) -> f64 {
+        let w = 2.0 * PI * frequency / SAMPLE_RATE;
+        let (re, im) = fir
+            .taps()
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (n, h)| {
+                (re + h * (w * n as f64).cos(), im - h * (w * n as f64).sin())
+            });
+        (re * re + im * im).sqrt()
+    }
+
+    fn max_error(fir: &Fir, band: (f64, f64), desired: f64) -> f64 {
+        (0..=200)
+            .map(|k| band.0 + (band.1 - band.0) * k as f64 / 200.0)
+            .map(|f| (gain(fir, f) - desired).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn windowed_designs_are_linear_phase() {
+        let lowpass = Fir::lowpass(SAMPLE_RATE, 2000.0, 101, WindowFunction::Hamming);
+        assert_eq!(lowpass.latency(), 50);
+        assert!((gain(&lowpass, 0.0) - 1.0).abs() < 1e-12);
+        // Hamming sidelobes stay about 53 dB down
+        assert!(max_error(&lowpass, (3500.0, 24000.0), 0.0) < 10f64.powf(-50.0 / 20.0));
+
+        let highpass = Fir::highpass(SAMPLE_RATE, 2000.0, 101, WindowFunction::Hamming);
+        assert!(gain(&highpass, 0.0) < 1e-12);
+        assert!((gain(&highpass, 24000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn band_designs_pass_and_stop() {
+        let bandpass = Fir::bandpass(SAMPLE_RATE, 2000.0, 6000.0, 201, WindowFunction::Blackman);
+        assert!((gain(&bandpass, 4000.0) - 1.0).abs() < 0.01);
+        assert!(gain(&bandpass, 0.0) < 0.01);
+        assert!(gain(&bandpass, 12000.0) < 0.01);
+        let bandstop = Fir::bandstop(SAMPLE_RATE, 2000.0, 6000.0, 201, WindowFunction::Blackman);
+        assert!(gain(&bandstop, 4000.0) < 0.01);
+        assert!((gain(&bandstop, 0.0) - 1.0).abs() < 0.01);
+        assert!((gain(&bandstop, 12000.0) - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn equiripple_errors_are_equal_and_weighted() {
+        let pass = (0.0, 4000.0);
+        let stop = (6000.0, 24000.0);
+        let fir = Fir::equiripple(SAMPLE_RATE, 51, &[pass, stop], &[1.0, 0.0], &[1.0, 1.0]);
+        assert_eq!(fir.taps().len(), 51);
+        assert_eq!(fir.latency(), 25);
+        let (ripple, attenuation) = (max_error(&fir, pass, 1.0), max_error(&fir, stop, 0.0));
+        assert!(ripple < 0.01, "ripple {}", ripple);
+        assert!((ripple / attenuation - 1.0).abs() < 0.05);
+
+        // Ten times the weight makes the stopband error ten times smaller
+        let fir = Fir::equiripple(SAMPLE_RATE, 51, &[pass, stop], &[1.0, 0.0], &[1.0, 10.0]);
+        let (ripple, attenuation) = (max_error(&fir, pass, 1.0), max_error(&fir, stop, 0.0));
+        assert!((ripple / attenuation / 10.0 - 1.0).abs() < 0.05);
+    }
+}
@@ -0,0 +1,528 @@
+use super::*;
+use rustfft::num_complex::Complex;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prototype {
+    Butterworth,
+    // Passband ripple in dB
+    ChebyshevI(f64),
+    // Stopband attenuation in dB, the cutoff is where the stopband begins
+    ChebyshevII(f64),
+    Bessel,
+}
+
+// Band pass and band stop designs double the order, `cutoff` is their lower
+// edge and the variant holds the upper one in Hz
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CascadeKind {
+    LowPass,
+    HighPass,
+    BandPass(f64),
+    BandStop(f64),
+}
+
+#[derive(Clone)]
+pub struct Cascade {
+    pub sections: Vec<Biquad>,
+}
+
+struct ZeroPoleGain {
+    zeros: Vec<Complex<f64>>,
+    poles: Vec<Complex<f64>>,
+    gain: f64,
+}
+
+impl Cascade {
+    pub fn new(sections: Vec<Biquad>) -> Self {
+        Self { sections }
+    }
+    pub fn design(
+        prototype: Prototype,
+        kind: CascadeKind,
+        order: usize,
+        sample_rate: f64,
+        cutoff: f64,
+    ) -> Self {
+        let analog = match prototype {
+            Prototype::Butterworth => butterworth(order),
+            Prototype::ChebyshevI(ripple) => chebyshev1(order, ripple),
+            Prototype::ChebyshevII(attenuation) => chebyshev2(order, attenuation),
+            Prototype::Bessel => bessel(order),
+        };
+        let warp = |cutoff: f64| 2.0 * sample_rate * (PI * cutoff / sample_rate).tan();
+        let analog = match kind {
+            CascadeKind::LowPass => analog.lowpass(warp(cutoff)),
+            CascadeKind::HighPass => analog.highpass(warp(cutoff)),
+            CascadeKind::BandPass(upper) => analog.bandpass(warp(cutoff), warp(upper)),
+            CascadeKind::BandStop(upper) => analog.bandstop(warp(cutoff), warp(upper)),
+        };
+        Self::new(analog.bilinear(sample_rate).sections())
+    }
+    pub fn butterworth(kind: CascadeKind, order: usize, sample_rate: f64, cutoff: f64) -> Self {
+        Self::design(Prototype::Butterworth, kind, order, sample_rate, cutoff)
+    }
+    pub fn chebyshev1(
+        kind: CascadeKind,
+        order: usize,
+        sample_rate: f64,
+        cutoff: f64,
+        ripple: f64,
+    ) -> Self {
+        Self::design(
+            Prototype::ChebyshevI(ripple),
+            kind,
+            order,
+            sample_rate,
+            cutoff,
+        )
+    }
+    pub fn chebyshev2(
+        kind: CascadeKind,
+        order: usize,
+        sample_rate: f64,
+        cutoff: f64,
+        attenuation: f64,
+    ) -> Self {
+        Self::design(
+            Prototype::ChebyshevII(attenuation),
+            kind,
+            order,
+            sample_rate,
+            cutoff,
+        )
+    }
+    pub fn bessel(kind: CascadeKind, order: usize, sample_rate: f64, cutoff: f64) -> Self {
+        Self::design(Prototype::Bessel, kind, order, sample_rate, cutoff)
+    }
+}
+
+impl Filter for Cascade {
+    fn apply(&mut self, sample: f64) -> f64 {
+        self.sections
+            .iter_mut()
+            .fold(sample, |sample, section| section.apply(sample))
+    }
+    fn reset(&mut self) {
+        self.sections.iter_mut().for_each(|s| s.reset());
+    }
+    fn process(&mut self, samples: &mut [f64]) {
+        self.sections.iter_mut().for_each(|s| s.process(samples));
+    }
+    fn tail(&self) -> usize {
+        self.sections
+            .iter()
+            .fold(0usize, |tail, s| tail.saturating_add(s.tail()))
+    }
+}
+
+fn product(values: &[Complex<f64>], offset: Complex<f64>) -> Complex<f64> {
+    values
+        .iter()
+        .fold(Complex::new(1.0, 0.0), |acc, v| acc * (offset - v))
+}
+
+fn butterworth(order: usize) -> ZeroPoleGain {
+    let n = order as f64;
+    ZeroPoleGain {
+        zeros: vec![],
+        poles: (1..=order)
+            .map(|k| Complex::from_polar(1.0, PI * (2.0 * k as f64 + n - 1.0) / (2.0 * n)))
+            .collect(),
+        gain: 1.0,
+    }
+}
+
+fn chebyshev_poles(order: usize, epsilon: f64) -> Vec<Complex<f64>> {
+    let n = order as f64;
+    let mu = (1.0 / epsilon).asinh() / n;
+    (1..=order)
+        .map(|k| {
+            let theta = PI * (2.0 * k as f64 - 1.0) / (2.0 * n);
+            Complex::new(-mu.sinh() * theta.sin(), mu.cosh() * theta.cos())
+        })
+        .collect()
+}
+
+fn chebyshev1(order: usize, ripple: f64) -> ZeroPoleGain {
+    let epsilon = (10f64.powf(ripple / 10.0) - 1.0).sqrt();
+    let poles = chebyshev_poles(order, epsilon);
+    let mut gain = product(&poles, Complex::new(0.0, 0.0)).re;
+    if order.is_multiple_of(2) {
+        gain /= (1.0 + epsilon * epsilon).sqrt();
+    }
+    ZeroPoleGain {
+        zeros: vec![],
+        poles,
+        gain,
+    }
+}
+
+fn chebyshev2(order: usize, attenuation: f64) -> ZeroPoleGain {
+    let epsilon = 1.0 / (10f64.powf(attenuation / 10.0) - 1.0).sqrt();
+    let n = order as f64;
+    let zeros: Vec<Complex<f64>> = (1..=order)
+        .filter(|k| 2 * k - 1 != order)
+        .map(|k| Complex::new(0.0, 1.0 / (PI * (2.0 * k as f64 - 1.0) / (2.0 * n)).cos()))
+        .collect();
+    let poles: Vec<Complex<f64>> = chebyshev_poles(order, epsilon)
+        .into_iter()
+        .map(|p| 1.0 / p.conj())
+        .collect();
+    let zero = Complex::new(0.0, 0.0);
+    let gain = (product(&poles, zero) / product(&zeros, zero)).re;
+    ZeroPoleGain { zeros, poles, gain }
+}
+
+// Roots of the reverse Bessel polynomial, scaled so the phase response
+// reaches its midpoint at the cutoff.
+fn bessel(order: usize) -> ZeroPoleGain {
+    let factorial = |n: usize| (1..=n).fold(1f64, |acc, k| acc * k as f64);
+    let coefficients: Vec<f64> = (0..=order)
+        .map(|k| {
+            factorial(2 * order - k)
+                / (2f64.powi((order - k) as i32) * factorial(k) * factorial(order - k))
+        })
+        .collect();
+    let poles = roots(&coefficients);
+    // The phase falls monotonically, bisect for the frequency where it is
+    // half of its final `-order * PI / 2`
+    let phase = |w: f64| -> f64 {
+        poles
+            .iter()
+            .map(|p| -(Complex::new(0.0, w) - p).arg())
+            .sum()
+    };
+    let target = -(order as f64) * PI / 4.0;
+    let (mut low, mut high) = (0.0, coefficients[0].powf(1.0 / order as f64));
+    while phase(high) > target {
+        high *= 2.0;
+    }
+    for _ in 0..100 {
+        let middle = (low + high) / 2.0;
+        if phase(middle) > target {
+            low = middle;
+        } else {
+            high = middle;
+        }
+    }
+    let poles: Vec<Complex<f64>> = poles.into_iter().map(|p| p / low).collect();
+    let gain = product(&poles, Complex::new(0.0, 0.0)).re;
+    ZeroPoleGain {
+        zeros: vec![],
+        poles,
+        gain,
+    }
+}
+
+// Durand-Kerner iteration, `coefficients` are in ascending powers
+fn roots(coefficients: &[f64]) -> Vec<Complex<f64>> {
+    let degree = coefficients.len() - 1;
+    let lead = coefficients[degree];
+    let eval = |x: Complex<f64>| {
+        coefficients
+            .iter()
+            .rev()
+            .fold(Complex::new(0.0, 0.0), |acc, c| acc * x + c / lead)
+    };
+    let radius = 1.0
+        + coefficients
+            .iter()
+            .map(|c| (c / lead).abs())
+            .fold(0.0, f64::max);
+    let mut roots: Vec<Complex<f64>> = (0..degree)
+        .map(|k| Complex::from_polar(radius, 0.4 + 2.0 * PI * k as f64 / degree as f64))
+        .collect();
+    for _ in 0..500 {
+        let mut change = 0f64;
+        for i in 0..degree {
+            let denominator = (0..degree)
+                .filter(|&j| j != i)
+                .fold(Complex::new(1.0, 0.0), |acc, j| acc * (roots[i] - roots[j]));
+            let step = eval(roots[i]) / denominator;
+            roots[i] -= step;
+            change = change.max(step.norm());
+        }
+        if change < 1e-14 {
+            break;
+        }
+    }
+    roots
+}
+
+impl ZeroPoleGain {
+    fn degree(&self) -> i32 {
+        self.poles.len() as i32 - self.zeros.len() as i32
+    }
+    fn lowpass(self, cutoff: f64) -> Self {
+        Self {
+            gain: self.gain * cutoff.powi(self.degree()),
+            zeros: self.zeros.iter().map(|z| z * cutoff).collect(),
+            poles: self.poles.iter().map(|p| p * cutoff).collect(),
+        }
+    }
+    fn highpass(self, cutoff: f64) -> Self {
+        let zero = Complex::new(0.0, 0.0);
+        let gain = self.gain * (product(&self.zeros, zero) / product(&self.poles, zero)).re;
+        let mut zeros: Vec<Complex<f64>> = self.zeros.iter().map(|z| cutoff / z).collect();
+        zeros.extend((0..self.degree()).map(|_| zero));
+        Self {
+            gain,
+            zeros,
+            poles: self.poles.iter().map(|p| cutoff / p).collect(),
+        }
+    }
+    // Every root `r` of the lowpass at `r * bandwidth / 2` splits into the two
+    // roots of `s^2 - 2 r s + w0^2`, so the band is centered on `w0`
+    fn bandpass(self, low: f64, high: f64) -> Self {
+        let (bandwidth, center) = (high - low, (low * high).sqrt());
+        let split = |roots: &[Complex<f64>]| -> Vec<Complex<f64>> {
+            roots
+                .iter()
+                .flat_map(|r| {
+                    let r = r * bandwidth / 2.0;
+                    let offset = (r * r - center * center).sqrt();
+                    [r + offset, r - offset]
+                })
+                .collect()
+        };
+        let mut zeros = split(&self.zeros);
+        zeros.extend((0..self.degree()).map(|_| Complex::new(0.0, 0.0)));
+        Self {
+            gain: self.gain * bandwidth.powi(self.degree()),
+            zeros,
+            poles: split(&self.poles),
+        }
+    }
+    // The bandpass transform of the highpass, with the zeros the highpass
+    // puts at 0 moved to the center of the band
+    fn bandstop(self, low: f64, high: f64) -> Self {
+        let (bandwidth, center) = (high - low, (low * high).sqrt());
+        let zero = Complex::new(0.0, 0.0);
+        let gain = self.gain * (product(&self.zeros, zero) / product(&self.poles, zero)).re;
+        let split = |roots: &[Complex<f64>]| -> Vec<Complex<f64>> {
+            roots
+                .iter()
+                .flat_map(|r| {
+                    let r = bandwidth / 2.0 / r;
+                    let offset = (r * r - center * center).sqrt();
+                    [r + offset, r - offset]
+                })
+                .collect()
+        };
+        let mut zeros = split(&self.zeros);
+        for _ in 0..self.degree() {
+            zeros.push(Complex::new(0.0, center));
+            zeros.push(Complex::new(0.0, -center));
+        }
+        Self {
+            gain,
+            zeros,
+            poles: split(&self.poles),
+        }
+    }
+    fn bilinear(self, sample_rate: f64) -> Self {
+        let fs2 = Complex::new(2.0 * sample_rate, 0.0);
+        let gain = self.gain * (product(&self.zeros, fs2) / product(&self.poles, fs2)).re;
+        let mut zeros: Vec<Complex<f64>> =
+            self.zeros.iter().map(|z| (fs2 + z) / (fs2 - z)).collect();
+        zeros.extend((0..self.degree()).map(|_| Complex::new(-1.0, 0.0)));
+        Self {
+            gain,
+            zeros,
+            poles: self.poles.iter().map(|p| (fs2 + p) / (fs2 - p)).collect(),
+        }
+    }
+    fn sections(&self) -> Vec<Biquad> {
+        let zeros = pair(&self.zeros);
+        let poles = pair(&self.poles);
+        zeros
+            .iter()
+            .zip(poles.iter())
+            .enumerate()
+            .map(|(i, (b, a))| {
+                let gain = if i == 0 { self.gain } else { 1.0 };
+                Biquad::from_coefficients(Coefficients {
+                    b0: gain,
+                    b1: gain * b.0,
+                    b2: gain * b.1,
+                    a1: a.0,
+                    a2: a.1,
+                })
+            })
+            .collect()
+    }
+}
+
+// Groups roots into second order polynomials `1 + c1 z^-1 + c2 z^-2`,
+// conjugate pairs first and then the real roots two by two.
+fn pair(roots: &[Complex<f64>]) -> Vec<(f64, f64)> {
+    let mut quadratics = Vec::new();
+    let mut reals = Vec::new();
+    for r in roots {
+        if r.im > 1e-9 {
+            quadratics.push((-2.0 * r.re, r.norm_sqr()));
+        } else if r.im.abs() <= 1e-9 {
+            reals.push(r.re);
+        }
+    }
+    for chunk in reals.chunks(2) {
+        quadratics.push(match chunk {
+            [a, b] => (-(a + b), a * b),
+            [a] => (-a, 0.0),
+            _ => unreachable!(),
+        });
+    }
+    quadratics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f64 = 48000.0;
+
+    fn response(cascade: &Cascade, frequency: f64) -> Complex<f64> {
+        let z = Complex::from_polar(1.0, -2.0 * PI * frequency / SAMPLE_RATE);
+        cascade
+            .sections
+            .iter()
+            .fold(Complex::new(1.0, 0.0), |h, s| {
+                let c = s.coefficients();
+                h * (c.b0 + c.b1 * z + c.b2 * z * z) / (1.0 + c.a1 * z + c.a2 * z * z)
+            })
+    }
+
+    fn db(cascade: &Cascade, frequency: f64) -> f64 {
+        20.0 * response(cascade, frequency).norm().log10()
+    }
+
+    #[test]
+    fn second_order_butterworth_matches_rbj_lowpass() {
+        let cascade = Cascade::butterworth(CascadeKind::LowPass, 2, SAMPLE_RATE, 1000.0);
+        let designed = cascade.sections[0].coefficients();
+        let expected = Coefficients::new(
+            BiquadKind::LowPass,
+            SAMPLE_RATE,
+            1000.0,
+            std::f64::consts::FRAC_1_SQRT_2,
+            0.0,
+        );
+        for (a, b) in [
+            (designed.b0, expected.b0),
+            (designed.b1, expected.b1),
+            (designed.b2, expected.b2),
+            (designed.a1, expected.a1),
+            (designed.a2, expected.a2),
+        ] {
+            assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn butterworth_is_3db_down_at_cutoff() {
+        for order in 1..=8 {
+            let lowpass = Cascade::butterworth(CascadeKind::LowPass, order, SAMPLE_RATE, 1000.0);
+            assert!(db(&lowpass, 0.0).abs() < 1e-9);
+            assert!((db(&lowpass, 1000.0) + 3.0103).abs() < 1e-3);
+            let highpass = Cascade::butterworth(CascadeKind::HighPass, order, SAMPLE_RATE, 1000.0);
+            assert!(db(&highpass, SAMPLE_RATE / 2.0).abs() < 1e-9);
+            assert!((db(&highpass, 1000.0) + 3.0103).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn butterworth_rolls_off_monotonically() {
+        let cascade = Cascade::butterworth(CascadeKind::LowPass, 4, SAMPLE_RATE, 1000.0);
+        let mut last = 0.0;
+        for k in 1..200 {
+            let level = db(&cascade, k as f64 * 100.0);
+            assert!(level < last);
+            last = level;
+        }
+        // 24 dB per octave, a little more once warped
+        assert!(db(&cascade, 4000.0) < -47.0);
+    }
+
+    #[test]
+    fn chebyshev1_ripple_stays_in_passband() {
+        for order in [3, 4, 5] {
+            let cascade =
+                Cascade::chebyshev1(CascadeKind::LowPass, order, SAMPLE_RATE, 1000.0, 1.0);
+            let passband: Vec<f64> = (0..=1000).map(|f| db(&cascade, f as f64)).collect();
+            let max = passband.iter().cloned().fold(f64::MIN, f64::max);
+            let min = passband.iter().cloned().fold(f64::MAX, f64::min);
+            assert!(max.abs() < 1e-6, "order {} peaks at {}", order, max);
+            assert!((min + 1.0).abs() < 1e-3, "order {} dips to {}", order, min);
+            assert!((db(&cascade, 1000.0) + 1.0).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn chebyshev2_reaches_stopband_attenuation() {
+        for order in [3, 4, 6] {
+            let cascade =
+                Cascade::chebyshev2(CascadeKind::LowPass, order, SAMPLE_RATE, 4000.0, 40.0);
+            assert!(db(&cascade, 0.0).abs() < 1e-6);
+            let stopband = (4000..24000)
+                .step_by(10)
+                .map(|f| db(&cascade, f as f64))
+                .fold(f64::MIN, f64::max);
+            assert!(
+                (stopband + 40.0).abs() < 1e-3,
+                "order {}: {}",
+                order,
+                stopband
+            );
+        }
+    }
+
+    #[test]
+    fn bessel_phase_is_half_way_at_cutoff() {
+        for order in 2..=6 {
+            let cascade = Cascade::bessel(CascadeKind::LowPass, order, SAMPLE_RATE, 1000.0);
+            assert!(db(&cascade, 0.0).abs() < 1e-6);
+            let mut phase = 0.0;
+            let mut last = response(&cascade, 0.0).arg();
+            for k in 1..=1000 {
+                let arg = response(&cascade, k as f64).arg();
+                let mut step = arg - last;
+                step -= 2.0 * PI * (step / (2.0 * PI)).round();
+                phase += step;
+                last = arg;
+            }
+            let expected = -(order as f64) * PI / 4.0;
+            assert!(
+                (phase - expected).abs() < 1e-4,
+                "order {}: {}",
+                order,
+                phase
+            );
+        }
+    }
+
+    #[test]
+    fn band_designs_are_3db_down_at_their_edges() {
+        // The center of the band in the warped frequencies
+        let warp = |f: f64| (PI * f / SAMPLE_RATE).tan();
+        let center = (warp(500.0) * warp(2000.0)).sqrt().atan() * SAMPLE_RATE / PI;
+        for order in 1..=4 {
+            let kind = CascadeKind::BandPass(2000.0);
+            let bandpass = Cascade::butterworth(kind, order, SAMPLE_RATE, 500.0);
+            assert_eq!(bandpass.sections.len(), order);
+            assert!(db(&bandpass, center).abs() < 1e-6);
+            assert!((db(&bandpass, 500.0) + 3.0103).abs() < 1e-3);
+            assert!((db(&bandpass, 2000.0) + 3.0103).abs() < 1e-3);
+            assert!(db(&bandpass, 20.0) < -20.0 * order as f64);
+            assert!(db(&bandpass, 20000.0) < -10.0 * order as f64);
+
+            let kind = CascadeKind::BandStop(2000.0);
+            let bandstop = Cascade::butterworth(kind, order, SAMPLE_RATE, 500.0);
+            assert!(db(&bandstop, center) < -100.0);
+            assert!((db(&bandstop, 500.0) + 3.0103).abs() < 1e-3);
+            assert!((db(&bandstop, 2000.0) + 3.0103).abs() < 1e-3);
+            assert!(db(&bandstop, 0.0).abs() < 1e-6);
+            assert!(db(&bandstop, SAMPLE_RATE / 2.0).abs() < 1e-6);
+        }
+    }
+}
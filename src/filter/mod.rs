@@ -1,5 +1,29 @@
+mod analysis;
+mod biquad;
+mod chain;
+mod delay;
+mod distortion;
+mod dynamics;
+mod echo;
+mod fir;
+mod iir;
+mod modulated;
+
+pub use analysis::*;
+pub use biquad::*;
+pub use chain::*;
+pub use delay::*;
+pub use distortion::*;
+pub use dynamics::*;
+pub use echo::*;
+pub use fir::*;
+pub use iir::*;
+pub use modulated::*;
+
 use crate::fft::fft;
+use dyn_clone::DynClone;
 use rustfft::num_complex::Complex;
+use std::collections::VecDeque;
 
 lazy_static::lazy_static! {
      static ref CONCERT_HALL_FILTER_FFT: Vec<Complex<f64>> ={
@@ -13,10 +37,49 @@ lazy_static::lazy_static! {
      };
 }
 
-pub trait Filter {
+pub trait Filter: DynClone + Send + Sync {
     fn apply(&mut self, sample: f64) -> f64;
+    // Clears the state so the next sample starts from silence
+    fn reset(&mut self) {}
+    fn process(&mut self, samples: &mut [f64]) {
+        for sample in samples.iter_mut() {
+            *sample = self.apply(*sample);
+        }
+    }
+    // Samples the output lags behind the input
+    fn latency(&self) -> usize {
+        0
+    }
+    // Samples the output keeps ringing after the input stops,
+    // `usize::MAX` if it never settles
+    fn tail(&self) -> usize {
+        0
+    }
 }
 
+pub type DynFilter = Box<dyn Filter>;
+
+dyn_clone::clone_trait_object!(Filter);
+
+impl Filter for DynFilter {
+    fn apply(&mut self, sample: f64) -> f64 {
+        self.as_mut().apply(sample)
+    }
+    fn reset(&mut self) {
+        self.as_mut().reset()
+    }
+    fn process(&mut self, samples: &mut [f64]) {
+        self.as_mut().process(samples)
+    }
+    fn latency(&self) -> usize {
+        self.as_ref().latency()
+    }
+    fn tail(&self) -> usize {
+        self.as_ref().tail()
+    }
+}
+
+#[derive(Clone)]
 pub struct Integrator {
     value: f64,
 }
@@ -32,8 +95,15 @@ impl Filter for Integrator {
         self.value += sample;
         self.value
     }
+    fn reset(&mut self) {
+        self.value = 0.0;
+    }
+    fn tail(&self) -> usize {
+        usize::MAX
+    }
 }
 
+#[derive(Clone)]
 pub struct Differentiator {
     value: f64,
 }
@@ -44,6 +114,12 @@ impl Filter for Differentiator {
         self.value = sample;
         diff
     }
+    fn reset(&mut self) {
+        self.value = 0.0;
+    }
+    fn tail(&self) -> usize {
+        1
+    }
 }
 
 impl Differentiator {
@@ -52,15 +128,18 @@ impl Differentiator {
     }
 }
 
+#[derive(Clone)]
 pub struct MovingAverage {
-    values: Vec<f64>,
+    values: VecDeque<f64>,
+    sum: f64,
     count: usize,
 }
 
 impl MovingAverage {
     pub fn new(count: usize) -> Self {
         Self {
-            values: Vec::new(),
+            values: VecDeque::new(),
+            sum: 0.0,
             count,
         }
     }
@@ -68,10 +147,21 @@ impl MovingAverage {
 
 impl Filter for MovingAverage {
     fn apply(&mut self, sample: f64) -> f64 {
-        self.values.push(sample);
+        self.values.push_back(sample);
+        self.sum += sample;
         while self.values.len() > self.count {
-            self.values.remove(0);
+            self.sum -= self.values.pop_front().unwrap();
         }
-        self.values.iter().sum::<f64>() / self.values.len() as f64
+        self.sum / self.values.len() as f64
+    }
+    fn reset(&mut self) {
+        self.values.clear();
+        self.sum = 0.0;
+    }
+    fn latency(&self) -> usize {
+        self.count.saturating_sub(1) / 2
+    }
+    fn tail(&self) -> usize {
+        self.count.saturating_sub(1)
     }
 }
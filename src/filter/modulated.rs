@@ -0,0 +1,241 @@
+use super::*;
+use crate::sampler::{Delay, DynSampler, Sine};
+use std::f64::consts::PI;
+
+// Delay line whose length follows an LFO, `lfo` is expected to swing
+// between -1 and 1 and `delay`/`depth` are in seconds
+#[derive(Clone)]
+pub struct ModulatedDelay {
+    pub delay: f64,
+    pub depth: f64,
+    pub feedback: f64,
+    pub interpolation: Interpolation,
+    lfo: DynSampler,
+    line: DelayLine,
+    sample_rate: f64,
+    position: usize,
+}
+
+impl ModulatedDelay {
+    pub fn new(sample_rate: f64, lfo: DynSampler, delay: f64, depth: f64) -> Self {
+        let length = ((delay + depth.abs()) * sample_rate).ceil() as usize + 4;
+        Self {
+            delay,
+            depth,
+            feedback: 0.0,
+            interpolation: Interpolation::Cubic,
+            lfo,
+            line: DelayLine::new(length),
+            sample_rate,
+            position: 0,
+        }
+    }
+    fn time(&self) -> f64 {
+        self.position as f64 / self.sample_rate
+    }
+}
+
+impl Filter for ModulatedDelay {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let delay = (self.delay + self.depth * self.lfo.sample(self.time())) * self.sample_rate;
+        // Reading one sample less since the current one isn't written yet
+        let out = self.line.read(delay - 1.0, self.interpolation);
+        self.line.write(sample + out * self.feedback);
+        self.position += 1;
+        out
+    }
+    fn reset(&mut self) {
+        self.line.reset();
+        self.position = 0;
+    }
+    fn tail(&self) -> usize {
+        let length = self.line.len();
+        if self.feedback.abs() < 1e-6 {
+            length
+        } else if self.feedback.abs() >= 1.0 {
+            usize::MAX
+        } else {
+            // Until the recirculating signal decays by 60 dB
+            (1e-3f64.ln() / self.feedback.abs().ln()).ceil() as usize * length
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Chorus {
+    pub mix: f64,
+    voices: Vec<ModulatedDelay>,
+}
+
+impl Chorus {
+    pub fn new(
+        sample_rate: f64,
+        voices: usize,
+        delay: f64,
+        depth: f64,
+        rate: f64,
+        mix: f64,
+    ) -> Self {
+        let voices = voices.max(1);
+        Self {
+            mix,
+            voices: (0..voices)
+                .map(|i| {
+                    // Spread the LFO phases evenly over one period
+                    let phase = i as f64 / voices as f64 / rate;
+                    ModulatedDelay::new(
+                        sample_rate,
+                        Delay::new(Sine::sin(rate), -phase),
+                        delay,
+                        depth,
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Filter for Chorus {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let count = self.voices.len() as f64;
+        let wet = self.voices.iter_mut().map(|v| v.apply(sample)).sum::<f64>() / count;
+        wet * self.mix + sample * (1.0 - self.mix)
+    }
+    fn reset(&mut self) {
+        self.voices.iter_mut().for_each(|v| v.reset());
+    }
+    fn tail(&self) -> usize {
+        self.voices.iter().map(|v| v.tail()).max().unwrap_or(0)
+    }
+}
+
+#[derive(Clone)]
+pub struct Flanger {
+    pub mix: f64,
+    line: ModulatedDelay,
+}
+
+impl Flanger {
+    pub fn new(
+        sample_rate: f64,
+        lfo: DynSampler,
+        delay: f64,
+        depth: f64,
+        feedback: f64,
+        mix: f64,
+    ) -> Self {
+        let mut line = ModulatedDelay::new(sample_rate, lfo, delay, depth);
+        line.feedback = feedback.clamp(-0.99, 0.99);
+        Self { mix, line }
+    }
+}
+
+impl Filter for Flanger {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let wet = self.line.apply(sample);
+        wet * self.mix + sample * (1.0 - self.mix)
+    }
+    fn reset(&mut self) {
+        self.line.reset();
+    }
+    fn tail(&self) -> usize {
+        self.line.tail()
+    }
+}
+
+// Pitch vibrato, only the delayed signal is heard
+#[derive(Clone)]
+pub struct Vibrato {
+    line: ModulatedDelay,
+}
+
+impl Vibrato {
+    pub fn new(sample_rate: f64, lfo: DynSampler, depth: f64) -> Self {
+        Self {
+            line: ModulatedDelay::new(sample_rate, lfo, depth, depth),
+        }
+    }
+}
+
+impl Filter for Vibrato {
+    fn apply(&mut self, sample: f64) -> f64 {
+        self.line.apply(sample)
+    }
+    fn reset(&mut self) {
+        self.line.reset();
+    }
+    fn latency(&self) -> usize {
+        (self.line.delay * self.line.sample_rate) as usize
+    }
+    fn tail(&self) -> usize {
+        self.line.tail()
+    }
+}
+
+// Cascade of first order all-pass stages whose break frequency sweeps
+// exponentially between `low` and `high` following the LFO
+#[derive(Clone)]
+pub struct Phaser {
+    pub mix: f64,
+    pub feedback: f64,
+    low: f64,
+    high: f64,
+    lfo: DynSampler,
+    stages: Vec<(f64, f64)>,
+    last: f64,
+    sample_rate: f64,
+    position: usize,
+}
+
+impl Phaser {
+    pub fn new(
+        sample_rate: f64,
+        lfo: DynSampler,
+        stages: usize,
+        low: f64,
+        high: f64,
+        feedback: f64,
+        mix: f64,
+    ) -> Self {
+        Self {
+            mix,
+            feedback: feedback.clamp(-0.99, 0.99),
+            low,
+            high,
+            lfo,
+            stages: vec![(0.0, 0.0); stages],
+            last: 0.0,
+            sample_rate,
+            position: 0,
+        }
+    }
+}
+
+impl Filter for Phaser {
+    fn apply(&mut self, sample: f64) -> f64 {
+        let t = self.position as f64 / self.sample_rate;
+        self.position += 1;
+        let sweep = (self.lfo.sample(t) + 1.0) / 2.0;
+        let frequency = self.low * (self.high / self.low).powf(sweep);
+        let tan = (PI * frequency / self.sample_rate).tan();
+        let a = (tan - 1.0) / (tan + 1.0);
+
+        let mut x = sample + self.last * self.feedback;
+        for (x1, y1) in self.stages.iter_mut() {
+            let y = a * x + *x1 - a * *y1;
+            *x1 = x;
+            *y1 = y;
+            x = y;
+        }
+        self.last = x;
+        x * self.mix + sample * (1.0 - self.mix)
+    }
+    fn reset(&mut self) {
+        self.stages.iter_mut().for_each(|s| *s = (0.0, 0.0));
+        self.last = 0.0;
+        self.position = 0;
+    }
+    fn tail(&self) -> usize {
+        (0.05 * self.sample_rate) as usize
+    }
+}
@@ -0,0 +1,249 @@
+use super::*;
+use crate::filter::{Biquad, Filter};
+use crate::notes::semitone;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrumSound {
+    Kick,
+    Snare,
+    ClosedHat,
+    OpenHat,
+    Clap,
+    LowTom,
+    MidTom,
+    HighTom,
+    Cymbal,
+}
+
+// The square oscillators of the TR-808 cymbal and hi-hats
+const METAL: [f64; 6] = [205.3, 304.4, 369.6, 522.7, 540.0, 800.0];
+const HATS: usize = 0;
+
+// Drum machine with one synthesized drum per note name, the octave doesn't
+// matter. The default map follows the General MIDI drum notes:
+// c kick, c+ cymbal, d snare, d+ clap, f low tom, f+ closed hat, a mid tom,
+// a+ open hat and b high tom.
+pub struct DrumKit {
+    sample_rate: f64,
+    parameters: Parameters,
+    map: [Option<DrumSound>; 12],
+}
+
+impl DrumKit {
+    pub fn new(sample_rate: f64) -> Self {
+        let mut kit = Self {
+            sample_rate,
+            parameters: Parameters::new(vec![
+                // In semitones, shifts every drum
+                Parameter::float("tune", -12.0, 12.0, 0.0),
+                Parameter::float("kick_pitch", 30.0, 120.0, 50.0),
+                Parameter::float("kick_sweep", 50.0, 500.0, 160.0),
+                Parameter::float("kick_decay", 0.05, 2.0, 0.4),
+                Parameter::float("kick_click", 0.0, 1.0, 0.3),
+                Parameter::float("snare_pitch", 100.0, 400.0, 180.0),
+                // Balance between the tone and the noise
+                Parameter::float("snare_snappy", 0.0, 1.0, 0.6),
+                Parameter::float("snare_decay", 0.05, 1.0, 0.2),
+                Parameter::float("hat_decay", 0.01, 0.5, 0.05),
+                Parameter::float("open_hat_decay", 0.1, 2.0, 0.4),
+                Parameter::float("clap_decay", 0.05, 1.0, 0.15),
+                Parameter::float("tom_pitch", 60.0, 300.0, 100.0),
+                Parameter::float("tom_decay", 0.05, 2.0, 0.3),
+                Parameter::float("cymbal_decay", 0.2, 5.0, 1.5),
+                Parameter::float("gain", 0.0, 2.0, 0.5),
+            ]),
+            map: [None; 12],
+        };
+        for (name, sound) in [
+            ("c", DrumSound::Kick),
+            ("c+", DrumSound::Cymbal),
+            ("d", DrumSound::Snare),
+            ("d+", DrumSound::Clap),
+            ("f", DrumSound::LowTom),
+            ("f+", DrumSound::ClosedHat),
+            ("a", DrumSound::MidTom),
+            ("a+", DrumSound::OpenHat),
+            ("b", DrumSound::HighTom),
+        ] {
+            kit.map(name, Some(sound));
+        }
+        kit
+    }
+    // Panics on an unknown note name
+    pub fn map(&mut self, name: &str, sound: Option<DrumSound>) {
+        let index = semitone(name).unwrap_or_else(|| panic!("Unknown note {}!", name));
+        self.map[index as usize] = sound;
+    }
+    pub fn sound(&self, event: &NoteEvent) -> Option<DrumSound> {
+        self.map[(event.key.round() as i64).rem_euclid(12) as usize]
+    }
+
+    fn render(&self, sound: DrumSound, seed: u64) -> Vec<f64> {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let tune = 2f64.powf(p.value("tune") / 12.0);
+        let noise = Noise::new(sample_rate, seed);
+        let decay = |t: f64, time: f64| (-t / time * 6.9).exp();
+        let length = |time: f64| (time * sample_rate) as usize;
+        let time = |i: usize| i as f64 / sample_rate;
+        // Sine falling exponentially from `from` to `to` Hz, its phase is
+        // the integral of the frequency
+        let sweep = |t: f64, from: f64, to: f64, time: f64| {
+            let phase = to * t + (from - to) * time * (1.0 - (-t / time).exp());
+            (2.0 * PI * phase * tune).sin()
+        };
+        let metal = |t: f64| {
+            METAL
+                .iter()
+                .map(|f| Waveform::Square(0.5).at(f * tune * t))
+                .sum::<f64>()
+                / METAL.len() as f64
+        };
+        match sound {
+            DrumSound::Kick => {
+                let (pitch, sweep_from) = (p.value("kick_pitch"), p.value("kick_sweep"));
+                let (release, click) = (p.value("kick_decay"), p.value("kick_click"));
+                (0..length(release))
+                    .map(|i| {
+                        let t = time(i);
+                        sweep(t, sweep_from, pitch, 0.04) * decay(t, release)
+                            + noise.sample(t) * click * decay(t, 0.004)
+                    })
+                    .collect()
+            }
+            DrumSound::Snare => {
+                let (pitch, release) = (p.value("snare_pitch"), p.value("snare_decay"));
+                let snappy = p.value("snare_snappy");
+                let mut filter = Biquad::highpass(sample_rate, 1500.0, 0.7);
+                (0..length(release))
+                    .map(|i| {
+                        let t = time(i);
+                        let tone = (sweep(t, pitch * 1.5, pitch, 0.01)
+                            + 0.5 * (2.0 * PI * pitch * tune * 1.83 * t).sin())
+                            * decay(t, release * 0.5);
+                        let snap = filter.apply(noise.sample(t)) * decay(t, release);
+                        tone * (1.0 - snappy) + snap * snappy
+                    })
+                    .collect()
+            }
+            DrumSound::ClosedHat | DrumSound::OpenHat | DrumSound::Cymbal => {
+                let (release, cutoff) = match sound {
+                    DrumSound::ClosedHat => (p.value("hat_decay"), 7000.0),
+                    DrumSound::OpenHat => (p.value("open_hat_decay"), 7000.0),
+                    _ => (p.value("cymbal_decay"), 4000.0),
+                };
+                let mut bandpass = Biquad::bandpass(sample_rate, cutoff * 1.4, 1.0);
+                let mut highpass = Biquad::highpass(sample_rate, cutoff, 0.7);
+                (0..length(release))
+                    .map(|i| {
+                        let t = time(i);
+                        let x = metal(t) + 0.2 * noise.sample(t);
+                        highpass.apply(bandpass.apply(x)) * decay(t, release) * 2.0
+                    })
+                    .collect()
+            }
+            DrumSound::Clap => {
+                let release = p.value("clap_decay");
+                let mut filter = Biquad::bandpass(sample_rate, 1200.0 * tune, 1.5);
+                (0..length(release + 0.03))
+                    .map(|i| {
+                        let t = time(i);
+                        // Three quick slaps, then the reverberant tail
+                        let slaps = [0.0, 0.01, 0.02]
+                            .iter()
+                            .filter(|&&start| t >= start)
+                            .map(|start| decay(t - start, 0.01))
+                            .sum::<f64>();
+                        let tail = if t >= 0.03 {
+                            decay(t - 0.03, release)
+                        } else {
+                            0.0
+                        };
+                        filter.apply(noise.sample(t)) * (slaps + tail) * 2.0
+                    })
+                    .collect()
+            }
+            DrumSound::LowTom | DrumSound::MidTom | DrumSound::HighTom => {
+                let ratio = match sound {
+                    DrumSound::LowTom => 1.0,
+                    DrumSound::MidTom => 1.4,
+                    _ => 1.9,
+                };
+                let pitch = p.value("tom_pitch") * ratio;
+                let release = p.value("tom_decay");
+                (0..length(release))
+                    .map(|i| {
+                        let t = time(i);
+                        sweep(t, pitch * 1.3, pitch, 0.05) * decay(t, release)
+                            + noise.sample(t) * 0.05 * decay(t, 0.01)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+impl Instrument for DrumKit {
+    fn name(&self) -> &str {
+        "drumkit"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let samples = match self.sound(event) {
+            Some(sound) => self
+                .render(sound, event.seed())
+                .into_iter()
+                .map(|s| s * event.velocity * self.parameters.value("gain"))
+                .collect(),
+            None => vec![],
+        };
+        Box::new(Record {
+            sample_rate: self.sample_rate,
+            samples,
+        })
+    }
+    // Drums ignore the gate and ring for their whole decay
+    fn release(&self) -> f64 {
+        let p = &self.parameters;
+        [
+            p.value("kick_decay"),
+            p.value("snare_decay"),
+            p.value("hat_decay"),
+            p.value("open_hat_decay"),
+            // The tail starts after the slaps
+            p.value("clap_decay") + 0.03,
+            p.value("tom_decay"),
+            p.value("cymbal_decay"),
+        ]
+        .into_iter()
+        .fold(0.0, f64::max)
+    }
+    fn choke_group(&self, event: &NoteEvent) -> Option<usize> {
+        match self.sound(event)? {
+            DrumSound::ClosedHat | DrumSound::OpenHat => Some(HATS),
+            _ => None,
+        }
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_silent_before_they_start() {
+        let kit = DrumKit::new(44100.0);
+        for key in [36.0, 38.0, 39.0, 41.0, 42.0, 46.0, 49.0] {
+            let event = NoteEvent::new(key, 1.0, 0.1).at(1.0);
+            let song = kit.voices().render(&kit, &[event]);
+            assert!((0..44100).all(|i| song.sample(i as f64 / 44100.0) == 0.0));
+            assert!((44100..46000).any(|i| song.sample(i as f64 / 44100.0) != 0.0));
+        }
+    }
+}
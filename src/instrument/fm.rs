@@ -0,0 +1,335 @@
+use super::*;
+use std::f64::consts::PI;
+use std::io;
+
+pub const OPERATORS: usize = 6;
+// Phase deviation in radians of a modulator at full level
+const MODULATION_INDEX: f64 = 4.0 * PI;
+
+// One routing of the six operators, numbered from 1 like on the DX7.
+// Modulators always have higher numbers than the operators they modulate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Algorithm {
+    // (modulator, target)
+    pub connections: &'static [(usize, usize)],
+    pub carriers: &'static [usize],
+    // (from, to), the output of `from` feeds back into `to`
+    pub feedback: (usize, usize),
+}
+
+const fn algorithm(
+    connections: &'static [(usize, usize)],
+    carriers: &'static [usize],
+    feedback: (usize, usize),
+) -> Algorithm {
+    Algorithm {
+        connections,
+        carriers,
+        feedback,
+    }
+}
+
+pub const ALGORITHMS: [Algorithm; 32] = [
+    algorithm(&[(2, 1), (4, 3), (5, 4), (6, 5)], &[1, 3], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 4), (6, 5)], &[1, 3], (2, 2)),
+    algorithm(&[(2, 1), (3, 2), (5, 4), (6, 5)], &[1, 4], (6, 6)),
+    algorithm(&[(2, 1), (3, 2), (5, 4), (6, 5)], &[1, 4], (4, 6)),
+    algorithm(&[(2, 1), (4, 3), (6, 5)], &[1, 3, 5], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (6, 5)], &[1, 3, 5], (5, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 3), (6, 5)], &[1, 3], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 3), (6, 5)], &[1, 3], (4, 4)),
+    algorithm(&[(2, 1), (4, 3), (5, 3), (6, 5)], &[1, 3], (2, 2)),
+    algorithm(&[(2, 1), (3, 2), (5, 4), (6, 4)], &[1, 4], (3, 3)),
+    algorithm(&[(2, 1), (3, 2), (5, 4), (6, 4)], &[1, 4], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 3), (6, 3)], &[1, 3], (2, 2)),
+    algorithm(&[(2, 1), (4, 3), (5, 3), (6, 3)], &[1, 3], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 4), (6, 4)], &[1, 3], (6, 6)),
+    algorithm(&[(2, 1), (4, 3), (5, 4), (6, 4)], &[1, 3], (2, 2)),
+    algorithm(&[(2, 1), (3, 1), (4, 3), (5, 1), (6, 5)], &[1], (6, 6)),
+    algorithm(&[(2, 1), (3, 1), (4, 3), (5, 1), (6, 5)], &[1], (2, 2)),
+    algorithm(&[(2, 1), (3, 1), (4, 1), (5, 4), (6, 5)], &[1], (3, 3)),
+    algorithm(&[(2, 1), (3, 2), (6, 4), (6, 5)], &[1, 4, 5], (6, 6)),
+    algorithm(&[(3, 1), (3, 2), (5, 4), (6, 4)], &[1, 2, 4], (3, 3)),
+    algorithm(&[(3, 1), (3, 2), (6, 4), (6, 5)], &[1, 2, 4, 5], (3, 3)),
+    algorithm(&[(2, 1), (6, 3), (6, 4), (6, 5)], &[1, 3, 4, 5], (6, 6)),
+    algorithm(&[(3, 2), (6, 4), (6, 5)], &[1, 2, 4, 5], (6, 6)),
+    algorithm(&[(6, 3), (6, 4), (6, 5)], &[1, 2, 3, 4, 5], (6, 6)),
+    algorithm(&[(6, 4), (6, 5)], &[1, 2, 3, 4, 5], (6, 6)),
+    algorithm(&[(3, 2), (5, 4), (6, 4)], &[1, 2, 4], (6, 6)),
+    algorithm(&[(3, 2), (5, 4), (6, 4)], &[1, 2, 4], (3, 3)),
+    algorithm(&[(2, 1), (4, 3), (5, 4)], &[1, 3, 6], (5, 5)),
+    algorithm(&[(4, 3), (6, 5)], &[1, 2, 3, 5], (6, 6)),
+    algorithm(&[(4, 3), (5, 4)], &[1, 2, 3, 6], (5, 5)),
+    algorithm(&[(6, 5)], &[1, 2, 3, 4, 5], (6, 6)),
+    algorithm(&[], &[1, 2, 3, 4, 5, 6], (6, 6)),
+];
+
+// Six sine operators doing phase modulation through one of the DX7
+// algorithms. Feedback needs the previous output, so notes are rendered
+// into a `Record` at `sample_rate`.
+pub struct Fm {
+    sample_rate: f64,
+    parameters: Parameters,
+}
+
+impl Fm {
+    pub fn new(sample_rate: f64) -> Self {
+        let mut parameters = vec![
+            Parameter::integer("algorithm", 1, 32, 5),
+            Parameter::integer("feedback", 0, 7, 0),
+            Parameter::integer("transpose", -24, 24, 0),
+            Parameter::float("gain", 0.0, 2.0, 0.3),
+        ];
+        // Electric piano like default: three carrier and modulator pairs
+        let ratios = [1.0, 1.0, 1.0, 14.0, 1.0, 1.0];
+        let levels = [1.0, 0.4, 1.0, 0.2, 0.8, 0.3];
+        for n in 1..=OPERATORS {
+            let name = |field: &str| format!("op{}_{}", n, field);
+            parameters.extend(vec![
+                Parameter::float(&name("ratio"), 0.5, 64.0, ratios[n - 1]),
+                Parameter::toggle(&name("fixed"), false),
+                Parameter::float(&name("frequency"), 1.0, 10000.0, 440.0),
+                Parameter::float(&name("detune"), -50.0, 50.0, 0.0),
+                Parameter::float(&name("level"), 0.0, 1.0, levels[n - 1]),
+                Parameter::float(&name("velocity"), 0.0, 1.0, 0.5),
+                Parameter::float(&name("attack"), 0.0, 20.0, 0.01),
+                Parameter::float(&name("decay"), 0.0, 20.0, 1.0),
+                Parameter::float(&name("sustain"), 0.0, 1.0, 0.5),
+                Parameter::float(&name("release"), 0.0, 20.0, 0.3),
+            ]);
+        }
+        parameters.extend(Glide::parameters());
+        Self {
+            sample_rate,
+            parameters: Parameters::new(parameters),
+        }
+    }
+
+    pub fn algorithm(&self) -> &'static Algorithm {
+        &ALGORITHMS[self.parameters.value("algorithm") as usize - 1]
+    }
+}
+
+impl Instrument for Fm {
+    fn name(&self) -> &str {
+        "fm"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let algorithm = self.algorithm();
+        let transpose = 2f64.powf(p.value("transpose") / 12.0);
+        let frequency = event.frequency_curve();
+        let value = |n: usize, field: &str| p.value(&format!("op{}_{}", n, field));
+        // Indexed by operator number, 0 is unused
+        let operators: Vec<(bool, f64, f64, DynSampler)> = (0..=OPERATORS)
+            .map(|n| {
+                if n == 0 {
+                    return (false, 0.0, 0.0, Const::new(0.0));
+                }
+                let detune = 2f64.powf(value(n, "detune") / 1200.0);
+                let fixed = value(n, "fixed") == 1.0;
+                let ratio = if fixed {
+                    value(n, "frequency") * detune
+                } else {
+                    value(n, "ratio") * detune * transpose
+                };
+                let sensitivity = value(n, "velocity");
+                let level = value(n, "level") * (1.0 - sensitivity * (1.0 - event.velocity));
+                let envelope = Envelope::adsr(
+                    value(n, "attack"),
+                    value(n, "decay"),
+                    value(n, "sustain"),
+                    value(n, "release"),
+                )
+                .gated(event.gate);
+                (fixed, ratio, level, envelope)
+            })
+            .collect();
+        let (from, to) = algorithm.feedback;
+        let feedback = match p.value("feedback") {
+            0.0 => 0.0,
+            amount => PI * 2f64.powf(amount - 7.0),
+        };
+        let gain = p.value("gain") * event.velocity / (algorithm.carriers.len() as f64).sqrt();
+
+        let length = ((event.gate + self.release()) * sample_rate).ceil() as usize;
+        let mut phases = [0.0; OPERATORS + 1];
+        let mut outputs = [0.0; OPERATORS + 1];
+        // Last two outputs of the feedback operator, averaged like the DX7
+        let mut history = [0.0; 2];
+        let mut samples = Vec::with_capacity(length);
+        for i in 0..length {
+            let t = i as f64 / sample_rate;
+            let note = frequency.sample(t);
+            for n in (1..=OPERATORS).rev() {
+                let (fixed, ratio, level, envelope) = &operators[n];
+                let mut modulation: f64 = algorithm
+                    .connections
+                    .iter()
+                    .filter(|c| c.1 == n)
+                    .map(|c| outputs[c.0] * MODULATION_INDEX)
+                    .sum();
+                if n == to {
+                    modulation += feedback * (history[0] + history[1]) / 2.0;
+                }
+                outputs[n] = (2.0 * PI * phases[n] + modulation).sin() * level * envelope.sample(t);
+                let step = if *fixed { *ratio } else { note * ratio } / sample_rate;
+                phases[n] = (phases[n] + step).fract();
+            }
+            history = [history[1], outputs[from]];
+            let carriers: f64 = algorithm.carriers.iter().map(|&n| outputs[n]).sum();
+            samples.push(carriers * gain);
+        }
+        Box::new(Record {
+            sample_rate,
+            samples,
+        })
+    }
+    fn release(&self) -> f64 {
+        (1..=OPERATORS)
+            .map(|n| self.parameters.value(&format!("op{}_release", n)))
+            .fold(0.0, f64::max)
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+// DX7 output and envelope levels (0 to 99) are roughly 0.75 dB per step
+fn dx7_level(level: u8) -> f64 {
+    if level == 0 {
+        0.0
+    } else {
+        2f64.powf((level.min(99) as f64 - 99.0) / 8.0)
+    }
+}
+
+// Approximate seconds an envelope rate takes over the full range
+fn dx7_rate(rate: u8) -> f64 {
+    (41.0 * 0.5f64.powf(rate.min(99) as f64 / 6.5)).min(20.0)
+}
+
+// Fields of one voice in the 155 byte unpacked layout
+struct Dx7Voice<'a> {
+    data: &'a [u8],
+}
+
+impl Dx7Voice<'_> {
+    // DX7 voices store operator 6 first
+    fn operator(&self, n: usize, field: usize) -> u8 {
+        self.data[(OPERATORS - n) * 21 + field]
+    }
+    fn preset(&self) -> (String, Preset) {
+        let name = String::from_utf8_lossy(&self.data[145..155])
+            .trim()
+            .to_string();
+        let mut values = vec![
+            ("algorithm".to_string(), (self.data[134] & 31) as f64 + 1.0),
+            ("feedback".to_string(), (self.data[135] & 7) as f64),
+            (
+                "transpose".to_string(),
+                (self.data[144].min(48) as f64 - 24.0),
+            ),
+        ];
+        for n in 1..=OPERATORS {
+            let op = |field: usize| self.operator(n, field);
+            let (rates, levels) = ([op(0), op(1), op(2), op(3)], [op(4), op(5), op(6), op(7)]);
+            let coarse = (op(18) & 31) as f64;
+            let fine = op(19).min(99) as f64;
+            let peak = dx7_level(levels[0]);
+            let sustain = if peak > 0.0 {
+                (dx7_level(levels[2]) / peak).min(1.0)
+            } else {
+                0.0
+            };
+            let field = |name: &str, value: f64| (format!("op{}_{}", n, name), value);
+            values.extend(vec![
+                field("fixed", (op(17) & 1) as f64),
+                field(
+                    "ratio",
+                    (if coarse == 0.0 { 0.5 } else { coarse } * (1.0 + fine / 100.0)).min(64.0),
+                ),
+                field(
+                    "frequency",
+                    (10f64.powf((op(18) & 3) as f64 + fine / 100.0)).clamp(1.0, 10000.0),
+                ),
+                field("detune", (op(20).min(14) as f64 - 7.0) * 1.5),
+                field("level", dx7_level(op(16))),
+                field("velocity", (op(15) & 7) as f64 / 7.0),
+                field("attack", dx7_rate(rates[0])),
+                field("decay", (dx7_rate(rates[1]) + dx7_rate(rates[2])).min(20.0)),
+                field("sustain", sustain),
+                field("release", dx7_rate(rates[3])),
+            ]);
+        }
+        (
+            name,
+            Preset {
+                instrument: "fm".to_string(),
+                values,
+            },
+        )
+    }
+}
+
+// Expands a voice of a 32 voice bank (128 bytes) to the unpacked layout
+fn unpack(packed: &[u8]) -> Vec<u8> {
+    let mut data = vec![0; 155];
+    for op in 0..OPERATORS {
+        let (src, dst) = (&packed[op * 17..op * 17 + 17], op * 21);
+        data[dst..dst + 11].copy_from_slice(&src[..11]);
+        data[dst + 11] = src[11] & 3;
+        data[dst + 12] = (src[11] >> 2) & 3;
+        data[dst + 13] = src[12] & 7;
+        data[dst + 14] = src[13] & 3;
+        data[dst + 15] = (src[13] >> 2) & 7;
+        data[dst + 16] = src[14];
+        data[dst + 17] = src[15] & 1;
+        data[dst + 18] = (src[15] >> 1) & 31;
+        data[dst + 19] = src[16];
+        data[dst + 20] = (src[12] >> 3) & 15;
+    }
+    data[126..134].copy_from_slice(&packed[102..110]);
+    data[134] = packed[110] & 31;
+    data[135] = packed[111] & 7;
+    data[136] = (packed[111] >> 3) & 1;
+    data[137..141].copy_from_slice(&packed[112..116]);
+    data[141] = packed[116] & 1;
+    data[142] = (packed[116] >> 1) & 7;
+    data[143] = (packed[116] >> 4) & 7;
+    data[144] = packed[117];
+    data[145..155].copy_from_slice(&packed[118..128]);
+    data
+}
+
+impl Fm {
+    // Reads a DX7 SysEx dump, either a single voice or a bank of 32, into
+    // named presets. Envelopes are approximated by ADSRs.
+    pub fn parse_sysex(data: &[u8]) -> io::Result<Vec<(String, Preset)>> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if data.len() < 6 || data[0] != 0xf0 || data[1] != 0x43 {
+            return Err(invalid("not a Yamaha SysEx message"));
+        }
+        let body = &data[6..];
+        match data[3] {
+            0 if body.len() >= 155 => Ok(vec![Dx7Voice { data: &body[..155] }.preset()]),
+            9 if body.len() >= 4096 => Ok(body[..4096]
+                .chunks(128)
+                .map(|packed| {
+                    Dx7Voice {
+                        data: &unpack(packed),
+                    }
+                    .preset()
+                })
+                .collect()),
+            _ => Err(invalid("unsupported DX7 SysEx format")),
+        }
+    }
+    pub fn load_sysex(path: &str) -> io::Result<Vec<(String, Preset)>> {
+        Self::parse_sysex(&std::fs::read(path)?)
+    }
+}
@@ -0,0 +1,230 @@
+use super::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlideTime {
+    // Seconds for every slide
+    Constant(f64),
+    // Semitones per second
+    Rate(f64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GlideCurve {
+    // Equal time for every semitone
+    Pitch,
+    // Equal time for every hertz
+    Frequency,
+}
+
+// Linear frequency slides are approximated by this many straight pitch
+// segments
+const FREQUENCY_STEPS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Glide {
+    pub time: GlideTime,
+    pub curve: GlideCurve,
+    // Only slide into notes that start while the previous key is held
+    pub legato: bool,
+}
+
+impl Glide {
+    pub fn new(time: GlideTime, curve: GlideCurve) -> Self {
+        Self {
+            time,
+            curve,
+            legato: false,
+        }
+    }
+    pub fn constant(time: f64) -> Self {
+        Self::new(GlideTime::Constant(time), GlideCurve::Pitch)
+    }
+    pub fn rate(semitones_per_second: f64) -> Self {
+        assert!(semitones_per_second > 0.0, "Glide rate must be positive!");
+        Self::new(GlideTime::Rate(semitones_per_second), GlideCurve::Pitch)
+    }
+    pub fn with_curve(mut self, curve: GlideCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+    pub fn legato_only(mut self) -> Self {
+        self.legato = true;
+        self
+    }
+
+    // Settings instruments add to their parameters to glide between notes,
+    // read back by `Glide::from_parameters`
+    pub fn parameters() -> Vec<Parameter> {
+        vec![
+            Parameter::choice("glide_mode", &["time", "rate"], 0),
+            // Seconds per slide in the time mode, 0 turns glide off
+            Parameter::float("glide", 0.0, 2.0, 0.0),
+            // Semitones per second in the rate mode, 0 turns glide off
+            Parameter::float("glide_rate", 0.0, 240.0, 0.0),
+            Parameter::choice("glide_curve", &["pitch", "frequency"], 0),
+            Parameter::toggle("glide_legato", false),
+        ]
+    }
+    // `None` when glide is off or the parameters have no glide settings
+    pub fn from_parameters(parameters: &Parameters) -> Option<Self> {
+        let mut glide = match parameters.get("glide_mode")? {
+            0.0 => Some(parameters.get("glide")?)
+                .filter(|&time| time > 0.0)
+                .map(Self::constant)?,
+            _ => Some(parameters.get("glide_rate")?)
+                .filter(|&rate| rate > 0.0)
+                .map(Self::rate)?,
+        };
+        if parameters.get("glide_curve") == Some(1.0) {
+            glide = glide.with_curve(GlideCurve::Frequency);
+        }
+        if parameters.get("glide_legato") == Some(1.0) {
+            glide = glide.legato_only();
+        }
+        Some(glide)
+    }
+
+    pub fn duration(&self, semitones: f64) -> f64 {
+        match self.time {
+            GlideTime::Constant(time) => time,
+            GlideTime::Rate(rate) => semitones.abs() / rate,
+        }
+    }
+
+    // Breakpoints in semitones going from `from` to `to`, starting at `start`
+    fn path(&self, start: f64, from: f64, to: f64) -> Vec<(f64, f64)> {
+        let duration = self.duration(to - from);
+        match self.curve {
+            GlideCurve::Pitch => vec![(start, from), (start + duration, to)],
+            GlideCurve::Frequency => {
+                let (f0, f1) = (2f64.powf(from / 12.0), 2f64.powf(to / 12.0));
+                (0..=FREQUENCY_STEPS)
+                    .map(|i| {
+                        let x = i as f64 / FREQUENCY_STEPS as f64;
+                        (start + duration * x, 12.0 * (f0 + (f1 - f0) * x).log2())
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    // Breakpoints and curves going from `from` at `start` onto the bend of
+    // `note` moved to `start` and up by `offset`. The slide is added on top
+    // of the bend, so bend points inside the slide keep their shape.
+    fn onto(
+        &self,
+        start: f64,
+        from: f64,
+        note: &NoteEvent,
+        offset: f64,
+    ) -> (Vec<(f64, f64)>, Vec<AutomationCurve>) {
+        let (points, curves) = bend_points(note);
+        let target = offset + points[0].1;
+        let path = self.path(start, from, target);
+        let end = path.last().unwrap().0 - start;
+        let mut times: Vec<f64> = path
+            .iter()
+            .map(|p| p.0 - start)
+            .chain(points.iter().map(|p| p.0).filter(|&t| t > 0.0 && t < end))
+            .collect();
+        times.sort_by(f64::total_cmp);
+        times.dedup();
+        let mut glided: Vec<(f64, f64)> = times
+            .into_iter()
+            .map(|t| {
+                let slide = along(&path, start + t) - target;
+                (start + t, offset + bend_at(note, t) + slide)
+            })
+            .collect();
+        let mut glided_curves = vec![AutomationCurve::Linear; glided.len() - 1];
+        let inside = points.iter().filter(|p| p.0 <= end).count();
+        glided_curves.extend(curves.iter().skip(inside.saturating_sub(1)));
+        glided.extend(
+            points
+                .iter()
+                .skip(inside)
+                .map(|&(t, v)| (t + start, v + offset)),
+        );
+        glided_curves.truncate(glided.len() - 1);
+        (glided, glided_curves)
+    }
+
+    // `note` starting `from` semitones away from its key and sliding home
+    pub fn slide(&self, note: NoteEvent, from: f64) -> NoteEvent {
+        let (points, curves) = self.onto(0.0, from, &note, 0.0);
+        note.with_bend(Automation::new(points, curves))
+    }
+
+    // Continues `first` with the pitch and bend of `next` instead of
+    // starting a new note
+    pub fn join(&self, first: &NoteEvent, next: &NoteEvent) -> NoteEvent {
+        let shift = next.time - first.time;
+        let (points, curves) = bend_points(first);
+        let kept = points.iter().filter(|p| p.0 < shift).count();
+        let mut joined: Vec<(f64, f64)> = points[..kept].to_vec();
+        let mut joined_curves: Vec<AutomationCurve> = curves[..kept.min(curves.len())].to_vec();
+        joined_curves.resize(joined.len(), AutomationCurve::Linear);
+        let (path, path_curves) =
+            self.onto(shift, bend_at(first, shift), next, next.key - first.key);
+        joined.extend(path);
+        joined_curves.extend(path_curves);
+        joined_curves.truncate(joined.len() - 1);
+        let mut note = first
+            .clone()
+            .with_bend(Automation::new(joined, joined_curves));
+        note.gate = next.end() - first.time;
+        note
+    }
+}
+
+// Value of a piecewise linear path at `t`, held before and after it
+fn along(path: &[(f64, f64)], t: f64) -> f64 {
+    let i = path.partition_point(|p| p.0 <= t);
+    if i == 0 {
+        return path[0].1;
+    }
+    if i == path.len() {
+        return path[i - 1].1;
+    }
+    let (a, b) = (path[i - 1], path[i]);
+    a.1 + (b.1 - a.1) * (t - a.0) / (b.0 - a.0)
+}
+
+// Bend of `note` at `t` semitones
+pub(crate) fn bend_at(note: &NoteEvent, t: f64) -> f64 {
+    note.bend.as_ref().map_or(0.0, |bend| bend.sample(t))
+}
+
+fn bend_points(note: &NoteEvent) -> (Vec<(f64, f64)>, Vec<AutomationCurve>) {
+    match &note.bend {
+        Some(bend) => (bend.points().to_vec(), bend.curves().to_vec()),
+        None => (vec![(0.0, 0.0)], vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glide_is_configured_from_parameters_and_mml() {
+        let mut synth = Subtractive::new(44100.0);
+        assert_eq!(synth.voices().glide, None);
+        synth.set("glide", 0.1).unwrap();
+        assert_eq!(synth.voices().glide, Some(Glide::constant(0.1)));
+        synth.set("glide_mode", 1.0).unwrap();
+        assert_eq!(synth.voices().glide, None);
+        synth.set("glide_rate", 24.0).unwrap();
+        assert_eq!(synth.voices().glide, Some(Glide::rate(24.0)));
+
+        let notes = crate::mml::parse("~+24c4g4~100c4~0g4");
+        let ends: Vec<Option<f64>> = notes
+            .iter()
+            .map(|n| n.bend.as_ref().map(|b| b.points().last().unwrap().0))
+            .collect();
+        assert_eq!(ends[0], None);
+        assert!((ends[1].unwrap() - 7.0 / 24.0).abs() < 1e-9);
+        assert!((ends[2].unwrap() - 0.1).abs() < 1e-9);
+        assert_eq!(ends[3], None);
+    }
+}
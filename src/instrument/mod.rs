@@ -1,52 +1,257 @@
+mod drumkit;
+mod fm;
+mod glide;
+mod modulation;
+mod note;
+mod parameter;
+mod registry;
+mod sfz;
+mod soundfont;
+mod subtractive;
+mod voice;
+mod waveguide;
+
+pub use drumkit::*;
+pub use fm::*;
+pub use glide::*;
+pub use modulation::*;
+pub use note::*;
+pub use parameter::*;
+pub use registry::*;
+pub use sfz::*;
+pub use soundfont::*;
+pub use subtractive::*;
+pub use voice::*;
+pub use waveguide::*;
+
 use crate::sampler::*;
 
-pub trait Instrument {
-    fn play(note: f64, length: f64, volume: f64) -> DynSampler;
+pub trait Instrument: Send + Sync {
+    fn name(&self) -> &str;
+    // The returned sampler starts at 0, not at `event.time`
+    fn play(&self, event: &NoteEvent) -> DynSampler;
+    // How long a note keeps sounding after its gate ends
+    fn release(&self) -> f64 {
+        0.0
+    }
+    // Glides when the parameters include `Glide::parameters`
+    fn voices(&self) -> VoiceAllocator {
+        match Glide::from_parameters(self.parameters()) {
+            Some(glide) => VoiceAllocator::default().with_glide(glide),
+            None => VoiceAllocator::default(),
+        }
+    }
+    // Starting a note cuts off every sounding note of its choke group
+    fn choke_group(&self, _event: &NoteEvent) -> Option<usize> {
+        None
+    }
+    fn parameters(&self) -> &Parameters;
+    fn parameters_mut(&mut self) -> &mut Parameters;
+
+    fn get(&self, name: &str) -> Option<f64> {
+        self.parameters().get(name)
+    }
+    fn set(&mut self, name: &str, value: f64) -> Result<(), ParameterError> {
+        self.parameters_mut().set(name, value)
+    }
+    fn preset(&self) -> Preset {
+        Preset {
+            instrument: self.name().to_string(),
+            values: self
+                .parameters()
+                .iter()
+                .map(|p| (p.name.clone(), p.value))
+                .collect(),
+        }
+    }
+    // Values missing from the preset are reset to their defaults
+    fn load_preset(&mut self, preset: &Preset) -> Result<(), ParameterError> {
+        let mut parameters = self.parameters().clone();
+        parameters.reset();
+        for (name, value) in preset.values.iter() {
+            parameters.set(name, *value)?;
+        }
+        *self.parameters_mut() = parameters;
+        Ok(())
+    }
 }
 
-pub struct Drum;
+pub struct Drum {
+    parameters: Parameters,
+}
+
+impl Drum {
+    pub fn new() -> Self {
+        Self {
+            parameters: Parameters::new(vec![
+                Parameter::float("pitch", 1.0, 128.0, 32.0),
+                Parameter::float("attack", 0.0, 1.0, 0.1),
+                Parameter::float("decay", 0.0, 1.0, 0.1),
+                Parameter::float("release", 0.0, 1.0, 0.1),
+                Parameter::float("gain", 0.0, 1.0, 0.2),
+            ]),
+        }
+    }
+}
+
+impl Default for Drum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Instrument for Drum {
-    fn play(note: f64, _length: f64, volume: f64) -> DynSampler {
+    fn name(&self) -> &str {
+        "drum"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
         let snd = AmplitudeModulator::new(
-            Sawtooth::new(note / 32.0),
-            Compound::adsr(0.1, 0.1, 0.0, 0.1, 0.1),
+            Sawtooth::new(event.frequency / p.value("pitch")),
+            Compound::adsr(
+                p.value("attack"),
+                p.value("decay"),
+                0.0,
+                p.value("release"),
+                0.1,
+            ),
         );
         Gain::new(
             FrequencyModulator::new(snd, Compound::adsr(0.05, 1.0, 0.05, 0.05, 0.1)),
-            0.2 * volume,
+            p.value("gain") * event.velocity,
         )
     }
+    // The drum ignores the gate, its whole envelope is the tail
+    fn release(&self) -> f64 {
+        let p = &self.parameters;
+        p.value("attack") + p.value("decay") + p.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+pub struct DummyInstrument {
+    parameters: Parameters,
+}
+
+impl DummyInstrument {
+    pub fn new() -> Self {
+        Self {
+            parameters: Parameters::new(vec![
+                Parameter::float("attack", 0.0, 1.0, 0.1),
+                Parameter::float("release", 0.0, 1.0, 0.1),
+                Parameter::float("vibrato", 0.1, 20.0, 5.0),
+                Parameter::float("gain", 0.0, 1.0, 0.1),
+            ]),
+        }
+    }
 }
 
-pub struct DummyInstrument;
+impl Default for DummyInstrument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Instrument for DummyInstrument {
-    fn play(note: f64, length: f64, volume: f64) -> DynSampler {
+    fn name(&self) -> &str {
+        "dummy"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
         let snd = AmplitudeModulator::new(
-            Sawtooth::new(note),
-            Compound::adsr(0.1, length, 0.0, 0.1, 0.1),
+            event.bent(Sawtooth::new(event.frequency)),
+            Compound::adsr(p.value("attack"), event.gate, 0.0, p.value("release"), 0.1),
         );
         Gain::new(
-            FrequencyModulator::new(snd, Window::new(Sine::sin(5.0), 1.05, 1.10)),
-            0.1 * volume,
+            FrequencyModulator::new(snd, Window::new(Sine::sin(p.value("vibrato")), 1.05, 1.10)),
+            p.value("gain") * event.velocity,
         )
     }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
 }
 
-pub struct LegitInstrument;
+pub struct LegitInstrument {
+    parameters: Parameters,
+    matrix: ModMatrix,
+}
+
+impl LegitInstrument {
+    pub fn new() -> Self {
+        let mut parameters = vec![
+            Parameter::integer("voices", 1, 16, 7),
+            Parameter::float("detune", 0.0, 100.0, 20.0),
+            Parameter::float("tremolo_rate", 0.0, 20.0, 4.0),
+            Parameter::float("tremolo_depth", 0.0, 0.5, 0.35),
+            Parameter::float("attack", 0.0, 1.0, 0.1),
+            Parameter::float("release", 0.0, 1.0, 0.1),
+            Parameter::float("gain", 0.0, 2.0, 1.0),
+        ];
+        parameters.extend(Glide::parameters());
+        Self {
+            parameters: Parameters::new(parameters),
+            matrix: ModMatrix::new(),
+        }
+    }
+    // Routes played on top of the tremolo, `gain` can be modulated through
+    // `Destination::Parameter`
+    pub fn with_matrix(mut self, matrix: ModMatrix) -> Self {
+        self.matrix = matrix;
+        self
+    }
+}
+
+impl Default for LegitInstrument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Instrument for LegitInstrument {
-    fn play(note: f64, length: f64, volume: f64) -> DynSampler {
-        Gain::new(
+    fn name(&self) -> &str {
+        "legit"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let (note, length, volume) = (event.frequency, event.gate, event.velocity);
+        let depth = p.value("tremolo_depth");
+        let mut matrix = self.matrix.clone();
+        let tremolo = matrix.add_lfo(Lfo::new(Waveform::Sine, p.value("tremolo_rate"), depth));
+        matrix.route(tremolo, Destination::Amplitude, 1.0);
+        let unison = Unison::new(p.value("voices") as usize, p.value("detune"));
+        AmplitudeModulator::new(
             AmplitudeModulator::new(
                 AmplitudeModulator::new(
-                    Compound::new(vec![(0.1, Compound::unison(note, 7, |f| Sine::sin(f)))]),
-                    Window::new(Sine::sin(4.0), 0.3, 1.0),
+                    event.bent(Compound::new(vec![(
+                        0.1,
+                        unison.build(note, |f| Sine::sin(f)),
+                    )])),
+                    matrix.apply(&Destination::Amplitude, 1.0 - depth, note, volume, length),
                 ),
-                Compound::adsr(0.1, length, 0.0, 0.1, 0.1),
+                Compound::adsr(p.value("attack"), length, 0.0, p.value("release"), 0.1),
             ),
-            volume,
+            Gain::new(matrix.parameter(p, "gain", note, volume, length), volume),
         )
     }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
 }
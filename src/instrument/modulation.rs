@@ -0,0 +1,120 @@
+use super::Parameters;
+use crate::notes::C;
+use crate::sampler::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Source {
+    Lfo(usize),
+    Envelope(usize),
+    // Between 0 and 1
+    Velocity,
+    // Octaves above middle C
+    Pitch,
+    Constant(f64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Destination {
+    // In semitones
+    Pitch,
+    // Added to the base gain
+    Amplitude,
+    // Added to an instrument parameter, see `ModMatrix::parameter`
+    Parameter(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Route {
+    pub source: Source,
+    pub destination: Destination,
+    pub amount: f64,
+}
+
+#[derive(Clone, Default)]
+pub struct ModMatrix {
+    pub lfos: Vec<Lfo>,
+    pub envelopes: Vec<Envelope>,
+    pub routes: Vec<Route>,
+}
+
+impl ModMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn add_lfo(&mut self, lfo: Lfo) -> Source {
+        self.lfos.push(lfo);
+        Source::Lfo(self.lfos.len() - 1)
+    }
+    pub fn add_envelope(&mut self, envelope: Envelope) -> Source {
+        self.envelopes.push(envelope);
+        Source::Envelope(self.envelopes.len() - 1)
+    }
+    pub fn route(&mut self, source: Source, destination: Destination, amount: f64) {
+        self.routes.push(Route {
+            source,
+            destination,
+            amount,
+        });
+    }
+
+    fn source(&self, source: Source, note: f64, velocity: f64, gate: f64) -> DynSampler {
+        match source {
+            Source::Lfo(i) => Box::new(self.lfos[i]),
+            Source::Envelope(i) => self.envelopes[i].gated(gate),
+            Source::Velocity => Const::new(velocity),
+            Source::Pitch => Const::new((note / C).log2()),
+            Source::Constant(value) => Const::new(value),
+        }
+    }
+
+    // `base` plus every source routed to `destination`, scaled by the
+    // amount of its route
+    pub fn apply(
+        &self,
+        destination: &Destination,
+        base: f64,
+        note: f64,
+        velocity: f64,
+        gate: f64,
+    ) -> DynSampler {
+        let mut samplers = vec![(1.0, Const::new(base))];
+        samplers.extend(
+            self.routes
+                .iter()
+                .filter(|r| &r.destination == destination)
+                .map(|r| (r.amount, self.source(r.source, note, velocity, gate))),
+        );
+        Compound::new(samplers)
+    }
+
+    // An instrument parameter as a curve over the note, the parameter's
+    // value plus every source routed to it
+    pub fn parameter(
+        &self,
+        parameters: &Parameters,
+        name: &str,
+        note: f64,
+        velocity: f64,
+        gate: f64,
+    ) -> DynSampler {
+        self.apply(
+            &Destination::Parameter(name.to_string()),
+            parameters.value(name),
+            note,
+            velocity,
+            gate,
+        )
+    }
+
+    // Frequency curve of a note, usable with `FrequencyModulator`. Pitch
+    // modulation is linearized around the note (2^(x/12) ~ 1 + x ln2/12),
+    // which is accurate for vibrato sized amounts and keeps the curve
+    // integrable.
+    pub fn frequency(&self, note: f64, velocity: f64, gate: f64) -> DynSampler {
+        let semitones = self.apply(&Destination::Pitch, 0.0, note, velocity, gate);
+        Compound::new(vec![
+            (note, Const::new(1.0)),
+            (note * 2f64.ln() / 12.0, semitones),
+        ])
+    }
+}
@@ -0,0 +1,121 @@
+use crate::notes::*;
+use crate::sampler::*;
+
+// A single note of a song. `time` is where it starts in the song, every
+// curve is relative to the start of the note.
+#[derive(Clone)]
+pub struct NoteEvent {
+    pub time: f64,
+    // MIDI note number, fractional for microtonal notes
+    pub key: f64,
+    pub frequency: f64,
+    // Between 0 and 1
+    pub velocity: f64,
+    // How long the key is held, the release tail comes after it
+    pub gate: f64,
+    pub channel: usize,
+    pub voice: usize,
+    // Earlier notes of the same key on the same channel, counted separately
+    // for every channel by `VoiceAllocator::allocate` for round robins
+    pub round_robin: usize,
+    // In semitones
+    pub bend: Option<Automation>,
+    // Between 0 and 1
+    pub pressure: Option<Automation>,
+}
+
+impl NoteEvent {
+    pub fn new(key: f64, velocity: f64, gate: f64) -> Self {
+        Self {
+            time: 0.0,
+            key,
+            frequency: midi_to_frequency(key),
+            velocity,
+            gate,
+            channel: 0,
+            voice: 0,
+            round_robin: 0,
+            bend: None,
+            pressure: None,
+        }
+    }
+    pub fn from_frequency(frequency: f64, velocity: f64, gate: f64) -> Self {
+        Self {
+            frequency,
+            ..Self::new(frequency_to_midi(frequency), velocity, gate)
+        }
+    }
+    pub fn at(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+    pub fn with_channel(mut self, channel: usize) -> Self {
+        self.channel = channel;
+        self
+    }
+    pub fn with_voice(mut self, voice: usize) -> Self {
+        self.voice = voice;
+        self
+    }
+    pub fn with_bend(mut self, bend: Automation) -> Self {
+        self.bend = Some(bend);
+        self
+    }
+    pub fn with_pressure(mut self, pressure: Automation) -> Self {
+        self.pressure = Some(pressure);
+        self
+    }
+
+    pub fn end(&self) -> f64 {
+        self.time + self.gate
+    }
+    // Seed for the noise of the note, notes starting together on other
+    // keys, channels or voices get different noise
+    pub fn seed(&self) -> u64 {
+        [self.key.to_bits(), self.channel as u64, self.voice as u64]
+            .iter()
+            .fold(mix(self.time.to_bits()), |seed, x| mix(seed ^ x))
+    }
+
+    // Frequency multiplier over time caused by the bend. Linear bends in
+    // semitones are exponential in frequency, so those segments stay exact.
+    pub fn bend_ratio(&self) -> Option<Automation> {
+        self.bend.as_ref().map(|bend| {
+            let points = bend
+                .points()
+                .iter()
+                .map(|&(t, semitones)| (t, 2f64.powf(semitones / 12.0)))
+                .collect();
+            let curves = bend
+                .curves()
+                .iter()
+                .map(|curve| match curve {
+                    AutomationCurve::Step => AutomationCurve::Step,
+                    _ => AutomationCurve::Exponential,
+                })
+                .collect();
+            Automation::new(points, curves)
+        })
+    }
+    // Frequency over time, usable with `FrequencyModulator` and a unit
+    // frequency oscillator
+    pub fn frequency_curve(&self) -> DynSampler {
+        match self.bend_ratio() {
+            Some(ratio) => Compound::new(vec![(self.frequency, Box::new(ratio))]),
+            None => Const::new(self.frequency),
+        }
+    }
+    pub fn pressure_curve(&self) -> DynSampler {
+        match &self.pressure {
+            Some(pressure) => Box::new(pressure.clone()),
+            None => Const::new(0.0),
+        }
+    }
+    // Applies the bend to a sampler already playing at `frequency`
+    pub fn bent(&self, sampler: DynSampler) -> DynSampler {
+        match self.bend_ratio() {
+            Some(ratio) => FrequencyModulator::new(sampler, Box::new(ratio)),
+            None => sampler,
+        }
+    }
+}
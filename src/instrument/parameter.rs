@@ -0,0 +1,207 @@
+use std::fmt;
+use std::io;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterKind {
+    Float,
+    Integer,
+    Toggle,
+    // The value is the index of the selected option
+    Choice(Vec<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    pub min: f64,
+    pub max: f64,
+    pub default: f64,
+    pub value: f64,
+}
+
+impl Parameter {
+    pub fn float(name: &str, min: f64, max: f64, default: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: ParameterKind::Float,
+            min,
+            max,
+            default,
+            value: default,
+        }
+    }
+    pub fn integer(name: &str, min: i64, max: i64, default: i64) -> Self {
+        Self {
+            kind: ParameterKind::Integer,
+            ..Self::float(name, min as f64, max as f64, default as f64)
+        }
+    }
+    pub fn toggle(name: &str, default: bool) -> Self {
+        Self {
+            kind: ParameterKind::Toggle,
+            ..Self::float(name, 0.0, 1.0, if default { 1.0 } else { 0.0 })
+        }
+    }
+    pub fn choice(name: &str, options: &[&str], default: usize) -> Self {
+        Self {
+            kind: ParameterKind::Choice(options.iter().map(|o| o.to_string()).collect()),
+            ..Self::float(name, 0.0, options.len() as f64 - 1.0, default as f64)
+        }
+    }
+
+    pub fn check(&self, value: f64) -> Result<(), ParameterError> {
+        if !(value >= self.min && value <= self.max) {
+            return Err(ParameterError::OutOfRange {
+                name: self.name.clone(),
+                value,
+                min: self.min,
+                max: self.max,
+            });
+        }
+        if self.kind != ParameterKind::Float && value.fract() != 0.0 {
+            return Err(ParameterError::NotWhole {
+                name: self.name.clone(),
+                value,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterError {
+    Unknown(String),
+    OutOfRange {
+        name: String,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    NotWhole {
+        name: String,
+        value: f64,
+    },
+}
+
+impl fmt::Display for ParameterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParameterError::Unknown(name) => write!(f, "unknown parameter `{}`", name),
+            ParameterError::OutOfRange {
+                name,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "`{}` must be between {} and {}, got {}",
+                name, min, max, value
+            ),
+            ParameterError::NotWhole { name, value } => {
+                write!(f, "`{}` must be a whole number, got {}", name, value)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParameterError {}
+
+impl From<ParameterError> for io::Error {
+    fn from(e: ParameterError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Parameters {
+    list: Vec<Parameter>,
+}
+
+impl Parameters {
+    pub fn new(list: Vec<Parameter>) -> Self {
+        Self { list }
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Parameter> {
+        self.list.iter()
+    }
+    pub fn find(&self, name: &str) -> Option<&Parameter> {
+        self.list.iter().find(|p| p.name == name)
+    }
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.find(name).map(|p| p.value)
+    }
+    // For instruments reading their own parameters
+    pub fn value(&self, name: &str) -> f64 {
+        self.get(name)
+            .unwrap_or_else(|| panic!("Unknown parameter {}!", name))
+    }
+    pub fn set(&mut self, name: &str, value: f64) -> Result<(), ParameterError> {
+        let parameter = self
+            .list
+            .iter_mut()
+            .find(|p| p.name == name)
+            .ok_or_else(|| ParameterError::Unknown(name.to_string()))?;
+        parameter.check(value)?;
+        parameter.value = value;
+        Ok(())
+    }
+    pub fn reset(&mut self) {
+        self.list.iter_mut().for_each(|p| p.value = p.default);
+    }
+}
+
+// Parameter values of an instrument, stored as text:
+//
+//     instrument = legit
+//     attack = 0.05
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preset {
+    pub instrument: String,
+    pub values: Vec<(String, f64)>,
+}
+
+impl Preset {
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", line + 1, msg),
+            )
+        };
+        let mut preset = Preset::default();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(i, "expected `name = value`"))?;
+            let (key, value) = (key.trim(), value.trim());
+            if key == "instrument" {
+                preset.instrument = value.to_string();
+            } else {
+                let value = value.parse().map_err(|_| invalid(i, "invalid number"))?;
+                preset.values.push((key.to_string(), value));
+            }
+        }
+        Ok(preset)
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "instrument = {}", self.instrument)?;
+        for (name, value) in self.values.iter() {
+            writeln!(f, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use super::*;
+use std::collections::BTreeMap;
+
+// Constructors get the sample rate of the registry
+type Constructor = Box<dyn Fn(f64) -> Box<dyn Instrument> + Send + Sync>;
+
+// Looks instruments up by name, `Registry::builtin` knows the built-in ones.
+// Instruments needing a sample rate are created at the registry's.
+pub struct Registry {
+    sample_rate: f64,
+    constructors: BTreeMap<String, Constructor>,
+}
+
+impl Registry {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            constructors: BTreeMap::new(),
+        }
+    }
+    pub fn builtin(sample_rate: f64) -> Self {
+        let mut registry = Self::new(sample_rate);
+        registry.register("drum", |_| Box::new(Drum::new()));
+        registry.register("drumkit", |rate| Box::new(DrumKit::new(rate)));
+        registry.register("fm", |rate| Box::new(Fm::new(rate)));
+        registry.register("dummy", |_| Box::new(DummyInstrument::new()));
+        registry.register("legit", |_| Box::new(LegitInstrument::new()));
+        registry.register("plucked", |rate| Box::new(PluckedString::new(rate)));
+        registry.register("bowed", |rate| Box::new(BowedString::new(rate)));
+        registry.register("pipe", |rate| Box::new(BlownPipe::new(rate)));
+        registry.register("subtractive", |rate| Box::new(Subtractive::new(rate)));
+        registry
+    }
+    pub fn sample_rate(&self) -> f64 {
+        self.sample_rate
+    }
+    pub fn register<F>(&mut self, name: &str, constructor: F)
+    where
+        F: Fn(f64) -> Box<dyn Instrument> + Send + Sync + 'static,
+    {
+        self.constructors
+            .insert(name.to_string(), Box::new(constructor));
+    }
+    pub fn names(&self) -> Vec<&str> {
+        self.constructors.keys().map(|k| k.as_str()).collect()
+    }
+    pub fn create(&self, name: &str) -> Option<Box<dyn Instrument>> {
+        self.constructors.get(name).map(|c| c(self.sample_rate))
+    }
+    pub fn from_preset(&self, preset: &Preset) -> Result<Box<dyn Instrument>, ParameterError> {
+        let mut instrument = self
+            .create(&preset.instrument)
+            .ok_or_else(|| ParameterError::Unknown(preset.instrument.clone()))?;
+        instrument.load_preset(preset)?;
+        Ok(instrument)
+    }
+}
@@ -0,0 +1,427 @@
+use super::*;
+use crate::filter::Interpolation;
+use crate::notes::semitone;
+use regex::Regex;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// MIDI key from a number or a note name like `c4`, `f#3` or `eb-1`,
+// `c4` being 60
+fn parse_key(value: &str) -> Option<f64> {
+    if let Ok(key) = value.parse::<f64>() {
+        return Some(key);
+    }
+    let value = value.to_lowercase();
+    let split = value
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c != '#' && c != 'b')
+        .map(|(i, _)| i)?;
+    let (name, octave) = value.split_at(split);
+    let name = name.replace('#', "+");
+    let name = format!("{}{}", &name[..1], name[1..].replace('b', "-"));
+    let octave: i32 = octave.parse().ok()?;
+    Some((12 * (octave + 1) + semitone(&name)?) as f64)
+}
+
+// A sample mapped to a key and velocity range, with everything inherited
+// from its group, master and global headers already applied
+#[derive(Clone)]
+pub struct Region {
+    pub samples: Arc<Vec<f64>>,
+    pub sample_rate: f64,
+    pub keys: (f64, f64),
+    // 1 to 127
+    pub velocities: (f64, f64),
+    pub pitch_keycenter: f64,
+    // In cents per key
+    pub pitch_keytrack: f64,
+    // In semitones
+    pub transpose: f64,
+    pub tune: f64,
+    // In decibels
+    pub volume: f64,
+    // In percent
+    pub amp_veltrack: f64,
+    // In samples
+    pub offset: f64,
+    pub loop_mode: LoopMode,
+    pub loop_points: (usize, usize),
+    // Delay, attack, hold, decay, sustain (0 to 1) and release
+    pub ampeg: [f64; 6],
+    pub seq_length: usize,
+    pub seq_position: usize,
+    pub random: (f64, f64),
+}
+
+impl Region {
+    fn new(opcodes: &HashMap<String, String>, base: &Path, cache: &mut Cache) -> io::Result<Self> {
+        let text = |name: &str| opcodes.get(name).map(String::as_str);
+        let number = |name: &str, default: f64| -> io::Result<f64> {
+            match text(name) {
+                Some(value) => value
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid value {} for {}", value, name))),
+                None => Ok(default),
+            }
+        };
+        let key = |name: &str, default: f64| -> io::Result<f64> {
+            match text(name) {
+                Some(value) => parse_key(value)
+                    .ok_or_else(|| invalid(format!("Invalid key {} for {}", value, name))),
+                None => Ok(default),
+            }
+        };
+
+        let name = text("sample").ok_or_else(|| invalid("Region without a sample".into()))?;
+        let path = base
+            .join(text("default_path").unwrap_or(""))
+            .join(name.replace('\\', "/"));
+        let (samples, wav) = cache.load(path)?;
+        let single = key("key", -1.0)?;
+        let (lokey, hikey, keycenter) = if single >= 0.0 {
+            (single, single, single)
+        } else {
+            (0.0, 127.0, 60.0)
+        };
+        let loop_mode = match text("loop_mode").or(text("loopmode")) {
+            Some("no_loop") => LoopMode::NoLoop,
+            Some("one_shot") => LoopMode::OneShot,
+            Some("loop_continuous") => LoopMode::Continuous,
+            Some("loop_sustain") => LoopMode::Sustain,
+            Some(mode) => return Err(invalid(format!("Invalid loop mode {}", mode))),
+            // Samples with a loop of their own loop by default
+            None if wav.loop_points.is_some() => LoopMode::Continuous,
+            None => LoopMode::NoLoop,
+        };
+        let (loop_start, loop_end) = wav.loop_points.unwrap_or((0, samples.len()));
+        let loop_start = number("loop_start", number("loopstart", loop_start as f64)?)?;
+        // The end opcode is inclusive
+        let loop_end = number("loop_end", number("loopend", loop_end as f64 - 1.0)?)? + 1.0;
+        Ok(Self {
+            samples,
+            sample_rate: wav.record.sample_rate,
+            keys: (key("lokey", lokey)?, key("hikey", hikey)?),
+            velocities: (number("lovel", 1.0)?, number("hivel", 127.0)?),
+            pitch_keycenter: key("pitch_keycenter", keycenter)?,
+            pitch_keytrack: number("pitch_keytrack", 100.0)?,
+            transpose: number("transpose", 0.0)?,
+            tune: number("tune", 0.0)?,
+            volume: number("volume", 0.0)?,
+            amp_veltrack: number("amp_veltrack", 100.0)?,
+            offset: number("offset", 0.0)?,
+            loop_mode,
+            loop_points: (loop_start.max(0.0) as usize, loop_end.max(0.0) as usize),
+            ampeg: [
+                number("ampeg_delay", 0.0)?,
+                number("ampeg_attack", 0.0)?,
+                number("ampeg_hold", 0.0)?,
+                number("ampeg_decay", 0.0)?,
+                number("ampeg_sustain", 100.0)? / 100.0,
+                number("ampeg_release", 0.0)?,
+            ],
+            seq_length: number("seq_length", 1.0)?.max(1.0) as usize,
+            seq_position: number("seq_position", 1.0)?.max(1.0) as usize,
+            random: (number("lorand", 0.0)?, number("hirand", 1.0)?),
+        })
+    }
+
+    fn matches(&self, key: f64, velocity: f64) -> bool {
+        (self.keys.0..=self.keys.1).contains(&key)
+            && (self.velocities.0..=self.velocities.1).contains(&velocity)
+    }
+
+    // How long the region sounds once its gate has closed
+    fn release(&self) -> f64 {
+        match self.loop_mode {
+            LoopMode::OneShot => self.samples.len() as f64 / self.sample_rate,
+            _ => self.ampeg[5],
+        }
+    }
+}
+
+// Every sample file is decoded once, however many regions use it
+#[derive(Default)]
+struct Cache(HashMap<PathBuf, (Arc<Vec<f64>>, Wav)>);
+
+impl Cache {
+    // The samples are moved out of the returned `Wav`
+    fn load(&mut self, path: PathBuf) -> io::Result<(Arc<Vec<f64>>, Wav)> {
+        if let Some(loaded) = self.0.get(&path) {
+            return Ok(loaded.clone());
+        }
+        let mut wav = Wav::load(&path.to_string_lossy())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        let samples = Arc::new(std::mem::take(&mut wav.record.samples));
+        self.0.insert(path, (samples.clone(), wav.clone()));
+        Ok((samples, wav))
+    }
+}
+
+// Sample player loading the regions of an SFZ file. Every region whose
+// key and velocity ranges contain the note plays, layered. Round robins
+// follow the `round_robin` count of the notes.
+pub struct Sfz {
+    parameters: Parameters,
+    regions: Vec<Region>,
+}
+
+impl Sfz {
+    pub fn new(regions: Vec<Region>) -> Self {
+        Self {
+            parameters: Parameters::new(vec![
+                Parameter::float("gain", 0.0, 2.0, 1.0),
+                // In semitones
+                Parameter::float("tune", -12.0, 12.0, 0.0),
+                Parameter::choice("interpolation", &["nearest", "linear", "cubic"], 2),
+            ]),
+            regions,
+        }
+    }
+
+    // Samples are looked up relative to `base`
+    pub fn parse(text: &str, base: &Path) -> io::Result<Self> {
+        let comment = Regex::new(r"(?s)/\*.*?\*/|//[^\n]*").unwrap();
+        let token = Regex::new(r"<(\w+)>|(\w+)=").unwrap();
+        let text = comment.replace_all(text, "");
+        let mut headers: [HashMap<String, String>; 4] = Default::default();
+        let mut region: Option<HashMap<String, String>> = None;
+        // Header the opcodes outside of regions go to, unknown ones are skipped
+        let mut current = Some(0);
+        let mut regions = vec![];
+        let mut cache = Cache::default();
+        let mut finish = |region: &mut Option<HashMap<String, String>>,
+                          headers: &[HashMap<String, String>; 4]|
+         -> io::Result<()> {
+            if let Some(opcodes) = region.take() {
+                let mut merged = HashMap::new();
+                headers
+                    .iter()
+                    .chain([&opcodes])
+                    .for_each(|h| merged.extend(h.clone()));
+                regions.push(Region::new(&merged, base, &mut cache)?);
+            }
+            Ok(())
+        };
+
+        let tokens: Vec<_> = token.captures_iter(&text).collect();
+        for (i, token) in tokens.iter().enumerate() {
+            let whole = token.get(0).unwrap();
+            if let Some(header) = token.get(1) {
+                finish(&mut region, &headers)?;
+                // Starting a header clears it and every narrower one
+                current = match header.as_str() {
+                    "control" => Some(0),
+                    "global" => Some(1),
+                    "master" => Some(2),
+                    "group" => Some(3),
+                    "region" => {
+                        region = Some(HashMap::new());
+                        continue;
+                    }
+                    _ => None,
+                };
+                if let Some(level) = current {
+                    headers[level..].iter_mut().for_each(|h| h.clear());
+                }
+                continue;
+            }
+            // Values run up to the next opcode, sample names may contain spaces
+            let end = tokens
+                .get(i + 1)
+                .map_or(text.len(), |t| t.get(0).unwrap().start());
+            let value = text[whole.end()..end].trim().to_string();
+            let name = token[2].to_string();
+            if let Some(opcodes) = region.as_mut() {
+                opcodes.insert(name, value);
+            } else if let Some(level) = current {
+                headers[level].insert(name, value);
+            }
+        }
+        finish(&mut region, &headers)?;
+        Ok(Self::new(regions))
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        let base = Path::new(path).parent().unwrap_or(Path::new(""));
+        Self::parse(&std::fs::read_to_string(path)?, base)
+    }
+
+    pub fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+}
+
+impl Instrument for Sfz {
+    fn name(&self) -> &str {
+        "sfz"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let key = event.key.round();
+        let velocity = (event.velocity * 127.0).round().clamp(1.0, 127.0);
+        let random = uniform(event.seed());
+        let interpolation = match p.value("interpolation") as usize {
+            0 => Interpolation::Nearest,
+            1 => Interpolation::Linear,
+            _ => Interpolation::Cubic,
+        };
+        let layers = self
+            .regions
+            .iter()
+            .filter(|r| r.matches(key, velocity))
+            .filter(|r| event.round_robin % r.seq_length + 1 == r.seq_position)
+            .filter(|r| r.random.0 <= random && random < r.random.1)
+            .map(|r| {
+                let semitones = (event.key - r.pitch_keycenter) * r.pitch_keytrack / 100.0
+                    + r.transpose
+                    + r.tune / 100.0
+                    + p.value("tune");
+                let mut playback = Playback::new(
+                    r.samples.clone(),
+                    r.sample_rate,
+                    2f64.powf(semitones / 12.0),
+                )
+                .with_loop(r.loop_mode, r.loop_points.0, r.loop_points.1)
+                .with_offset(r.offset)
+                .with_gate(event.gate);
+                playback.interpolation = interpolation;
+                // One shots ignore the gate and play the whole sample
+                let gate = match r.loop_mode {
+                    LoopMode::OneShot => playback.length(),
+                    _ => event.gate,
+                };
+                let [delay, attack, hold, decay, sustain, release] = r.ampeg;
+                let envelope =
+                    Envelope::dahdsr(delay, attack, hold, decay, sustain, release).gated(gate);
+                let tracking = r.amp_veltrack / 100.0;
+                let gain = 10f64.powf(r.volume / 20.0)
+                    * (1.0 - tracking + tracking * event.velocity * event.velocity)
+                    * p.value("gain");
+                (
+                    gain,
+                    AmplitudeModulator::new(event.bent(Box::new(playback)), envelope),
+                )
+            })
+            .collect();
+        Compound::new(layers)
+    }
+    fn release(&self) -> f64 {
+        self.regions.iter().map(Region::release).fold(0.0, f64::max)
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::wav_tests::{chunk, fmt, riff};
+
+    const SFZ: &str = "
+        // Line comment
+        <control> default_path=samples/
+        <global> ampeg_release=0.5 /* block
+        comment */
+        <master> volume=-6
+        <group> lokey=c4 hikey=b4 seq_length=2
+        <region> sample=tone a.wav seq_position=1 pitch_keycenter=a4
+        <region> sample=tone b.wav seq_position=2
+            loop_mode=loop_continuous loop_start=1 loop_end=2
+        <group> key=eb-1
+        <region> sample=tone a.wav transpose=2 pitch_keytrack=50
+        <master>
+        <region> sample=tone b.wav
+    ";
+
+    fn write_samples(dir: &Path) {
+        let samples = dir.join("samples");
+        std::fs::create_dir_all(&samples).unwrap();
+        for (name, value) in [("tone a.wav", 0x2000u16), ("tone b.wav", 0x4000)] {
+            let data: Vec<u8> = [value; 8].iter().flat_map(|s| s.to_le_bytes()).collect();
+            let wav = riff(&[chunk(b"fmt ", &fmt(1, 1, 16)), chunk(b"data", &data)]);
+            std::fs::write(samples.join(name), wav).unwrap();
+        }
+    }
+
+    #[test]
+    fn parses_note_names() {
+        assert_eq!(parse_key("60"), Some(60.0));
+        assert_eq!(parse_key("c4"), Some(60.0));
+        assert_eq!(parse_key("C#4"), Some(61.0));
+        assert_eq!(parse_key("eb-1"), Some(3.0));
+        assert_eq!(parse_key("b-1"), Some(11.0));
+        assert_eq!(parse_key("bb3"), Some(58.0));
+        assert_eq!(parse_key("h4"), None);
+    }
+
+    #[test]
+    fn regions_inherit_from_their_headers() {
+        let dir = std::env::temp_dir().join(format!("debuzzy-sfz-{}", std::process::id()));
+        write_samples(&dir);
+        let sfz = Sfz::parse(SFZ, &dir).unwrap();
+        let regions = sfz.regions();
+        assert_eq!(regions.len(), 4);
+
+        let first = &regions[0];
+        assert_eq!(first.keys, (60.0, 71.0));
+        assert_eq!(first.pitch_keycenter, 69.0);
+        assert_eq!(first.volume, -6.0);
+        assert_eq!(first.ampeg[5], 0.5);
+        assert_eq!((first.seq_length, first.seq_position), (2, 1));
+        assert_eq!(first.loop_mode, LoopMode::NoLoop);
+        assert_eq!(first.loop_points, (0, 8));
+        assert_eq!(first.samples[0], 0.25);
+
+        // The loop end is inclusive
+        assert_eq!(regions[1].loop_mode, LoopMode::Continuous);
+        assert_eq!(regions[1].loop_points, (1, 3));
+        assert_eq!(regions[1].samples[0], 0.5);
+
+        // A new group clears the previous one, `key` sets the range and center
+        let third = &regions[2];
+        assert_eq!((third.keys, third.pitch_keycenter), ((3.0, 3.0), 3.0));
+        assert_eq!((third.seq_length, third.volume), (1, -6.0));
+        assert_eq!((third.transpose, third.pitch_keytrack), (2.0, 50.0));
+
+        // A new master clears the group too, the global header stays
+        let last = &regions[3];
+        assert_eq!(
+            (last.keys, last.volume, last.ampeg[5]),
+            ((0.0, 127.0), 0.0, 0.5)
+        );
+
+        let missing = Sfz::parse("<region> sample=missing.wav", &dir);
+        assert!(missing.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn round_robins_alternate_per_key_and_channel() {
+        let dir = std::env::temp_dir().join(format!("debuzzy-rr-{}", std::process::id()));
+        write_samples(&dir);
+        let sfz = Sfz::parse(SFZ, &dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        let events = [
+            NoteEvent::new(64.0, 1.0, 0.1),
+            NoteEvent::new(64.0, 1.0, 0.1).at(0.5).with_channel(1),
+            NoteEvent::new(64.0, 1.0, 0.1).at(1.0),
+        ];
+        let mut voices = VoiceAllocator::default().allocate(&events, &sfz);
+        voices.sort_by(|a, b| a.event.time.total_cmp(&b.event.time));
+        let counts: Vec<usize> = voices.iter().map(|v| v.event.round_robin).collect();
+        assert_eq!(counts, vec![0, 0, 1]);
+        let first = sfz.play(&voices[0].event).sample(2.0 / 44100.0);
+        let second = sfz.play(&voices[2].event).sample(2.0 / 44100.0);
+        assert!(first > 0.0 && second > first);
+    }
+}
@@ -0,0 +1,892 @@
+use super::*;
+use crate::filter::{Biquad, Filter, Interpolation};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+const GENERATORS: usize = 61;
+// Generator numbers of the SoundFont 2.04 specification
+const START_OFFSET: usize = 0;
+const END_OFFSET: usize = 1;
+const LOOP_START_OFFSET: usize = 2;
+const LOOP_END_OFFSET: usize = 3;
+const START_COARSE_OFFSET: usize = 4;
+const FILTER_CUTOFF: usize = 8;
+const FILTER_Q: usize = 9;
+const MOD_ENV_TO_FILTER: usize = 11;
+const END_COARSE_OFFSET: usize = 12;
+const MOD_ENV: usize = 25;
+const VOL_ENV: usize = 33;
+const KEY_TO_VOL_HOLD: usize = 39;
+const KEY_TO_VOL_DECAY: usize = 40;
+const INSTRUMENT: usize = 41;
+const KEY_RANGE: usize = 43;
+const VELOCITY_RANGE: usize = 44;
+const LOOP_START_COARSE_OFFSET: usize = 45;
+const KEY_NUMBER: usize = 46;
+const VELOCITY: usize = 47;
+const ATTENUATION: usize = 48;
+const LOOP_END_COARSE_OFFSET: usize = 50;
+const COARSE_TUNE: usize = 51;
+const FINE_TUNE: usize = 52;
+const SAMPLE_ID: usize = 53;
+const SAMPLE_MODES: usize = 54;
+const SCALE_TUNING: usize = 56;
+const EXCLUSIVE_CLASS: usize = 57;
+const ROOT_KEY: usize = 58;
+// Generators a preset zone can't offset
+const ABSOLUTE: [usize; 16] = [
+    START_OFFSET,
+    END_OFFSET,
+    LOOP_START_OFFSET,
+    LOOP_END_OFFSET,
+    START_COARSE_OFFSET,
+    END_COARSE_OFFSET,
+    INSTRUMENT,
+    KEY_RANGE,
+    VELOCITY_RANGE,
+    LOOP_START_COARSE_OFFSET,
+    KEY_NUMBER,
+    VELOCITY,
+    LOOP_END_COARSE_OFFSET,
+    SAMPLE_ID,
+    SAMPLE_MODES,
+    ROOT_KEY,
+];
+const FILTER_OPEN: f64 = 13500.0;
+const CONTROL_BLOCK: usize = 16;
+// Decay and release are linear in decibels, which this approximates
+const DECAY_CURVE: f64 = -4.0;
+
+fn defaults() -> [i32; GENERATORS] {
+    let mut generators = [0; GENERATORS];
+    generators[FILTER_CUTOFF] = FILTER_OPEN as i32;
+    for stage in [0, 1, 2, 3, 5] {
+        generators[MOD_ENV + stage] = -12000;
+        generators[VOL_ENV + stage] = -12000;
+    }
+    // Delays of the modulation and vibrato LFOs
+    generators[21] = -12000;
+    generators[23] = -12000;
+    generators[KEY_RANGE] = 127 << 8;
+    generators[VELOCITY_RANGE] = 127 << 8;
+    generators[KEY_NUMBER] = -1;
+    generators[VELOCITY] = -1;
+    generators[SCALE_TUNING] = 100;
+    generators[ROOT_KEY] = -1;
+    generators
+}
+
+fn range(amount: i32) -> (f64, f64) {
+    ((amount & 0xff) as f64, ((amount >> 8) & 0xff) as f64)
+}
+
+fn timecents(value: f64) -> f64 {
+    2f64.powf(value / 1200.0)
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn u16_at(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn u32_at(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+fn name(data: &[u8]) -> String {
+    let end = data.iter().position(|&c| c == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+// Sub-chunks of a RIFF list as (id, body)
+fn chunks(mut data: &[u8]) -> io::Result<Vec<(&[u8], &[u8])>> {
+    let mut chunks = vec![];
+    while data.len() >= 8 {
+        let size = u32_at(data, 4) as usize;
+        let body = data
+            .get(8..8 + size)
+            .ok_or_else(|| invalid("truncated SoundFont chunk"))?;
+        chunks.push((&data[..4], body));
+        data = &data[(8 + size + size % 2).min(data.len())..];
+    }
+    Ok(chunks)
+}
+
+// Fixed size records of a `pdta` chunk
+fn records<'a>(
+    chunks: &HashMap<&[u8], &'a [u8]>,
+    id: &[u8],
+    size: usize,
+) -> io::Result<Vec<&'a [u8]>> {
+    let body = chunks
+        .get(id)
+        .ok_or_else(|| invalid(&format!("no {} chunk", String::from_utf8_lossy(id))))?;
+    if body.len() % size != 0 || body.len() < size {
+        return Err(invalid(&format!(
+            "malformed {} chunk",
+            String::from_utf8_lossy(id)
+        )));
+    }
+    Ok(body.chunks_exact(size).collect())
+}
+
+// Offsets a generator by a controller value scaled by `amount`, evaluated
+// once when a note starts
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modulator {
+    pub source: u16,
+    pub destination: u16,
+    pub amount: f64,
+    pub amount_source: u16,
+    pub transform: u16,
+}
+
+// The default modulators of the specification that depend on the velocity
+const DEFAULT_MODULATORS: [Modulator; 2] = [
+    Modulator {
+        source: 0x0502,
+        destination: ATTENUATION as u16,
+        amount: 960.0,
+        amount_source: 0,
+        transform: 0,
+    },
+    Modulator {
+        source: 0x0102,
+        destination: FILTER_CUTOFF as u16,
+        amount: -2400.0,
+        amount_source: 0,
+        transform: 0,
+    },
+];
+
+impl Modulator {
+    fn parse(record: &[u8]) -> Self {
+        Self {
+            source: u16_at(record, 0),
+            destination: u16_at(record, 2),
+            amount: u16_at(record, 4) as i16 as f64,
+            amount_source: u16_at(record, 6),
+            transform: u16_at(record, 8),
+        }
+    }
+    fn same(&self, other: &Modulator) -> bool {
+        self.source == other.source
+            && self.destination == other.destination
+            && self.amount_source == other.amount_source
+    }
+
+    // Only the key and velocity are known when a note starts, sources on
+    // MIDI controllers, pressure or the pitch wheel are not supported
+    fn input(source: u16, key: f64, velocity: f64) -> Option<f64> {
+        let concave = |x: f64| {
+            if x >= 1.0 {
+                1.0
+            } else {
+                (-40.0 / 96.0 * (1.0 - x).log10()).min(1.0)
+            }
+        };
+        let x = match (source & 0x80 != 0, source & 0x7f) {
+            // No controller
+            (false, 0) => return Some(1.0),
+            (false, 2) => velocity / 128.0,
+            (false, 3) => key / 128.0,
+            _ => return None,
+        };
+        let x = if source & 0x100 != 0 { 1.0 - x } else { x };
+        let bipolar = source & 0x200 != 0;
+        let (x, sign) = match bipolar {
+            true if x < 0.5 => (1.0 - 2.0 * x, -1.0),
+            true => (2.0 * x - 1.0, 1.0),
+            false => (x, 1.0),
+        };
+        let y = match source >> 10 {
+            0 => x,
+            1 => concave(x),
+            2 => 1.0 - concave(1.0 - x),
+            _ if bipolar => 1.0,
+            _ => (x >= 0.5) as u8 as f64,
+        };
+        Some(sign * y)
+    }
+    // Generator and amount added to it, `None` for unsupported modulators
+    fn value(&self, key: f64, velocity: f64) -> Option<(usize, f64)> {
+        let destination = self.destination as usize;
+        if destination >= GENERATORS {
+            return None;
+        }
+        let value = self.amount
+            * Self::input(self.source, key, velocity)?
+            * Self::input(self.amount_source, key, velocity)?;
+        Some((
+            destination,
+            if self.transform == 2 {
+                value.abs()
+            } else {
+                value
+            },
+        ))
+    }
+}
+
+// Modulators of a zone replace identical ones of its global zone or the
+// defaults
+fn merge(modulators: &mut Vec<Modulator>, zone: &[Modulator]) {
+    for modulator in zone {
+        match modulators.iter_mut().find(|m| m.same(modulator)) {
+            Some(m) => *m = *modulator,
+            None => modulators.push(*modulator),
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+struct RawZone {
+    generators: Vec<(usize, i32)>,
+    modulators: Vec<Modulator>,
+}
+
+impl RawZone {
+    fn get(&self, generator: usize) -> Option<i32> {
+        self.generators
+            .iter()
+            .find(|(g, _)| *g == generator)
+            .map(|(_, amount)| *amount)
+    }
+    fn apply(&self, generators: &mut [i32; GENERATORS]) {
+        for &(g, amount) in self.generators.iter() {
+            generators[g] = amount;
+        }
+    }
+}
+
+// Zones of every header, the last record of the header list only ends the
+// previous one
+fn zones(
+    headers: &[&[u8]],
+    bag_at: usize,
+    bags: &[&[u8]],
+    generators: &[&[u8]],
+    modulators: &[&[u8]],
+) -> io::Result<Vec<Vec<RawZone>>> {
+    let bag = |header: &[u8]| u16_at(header, bag_at) as usize;
+    headers
+        .windows(2)
+        .map(|pair| {
+            let bags = bags
+                .get(bag(pair[0])..=bag(pair[1]))
+                .ok_or_else(|| invalid("zone index out of range"))?;
+            bags.windows(2)
+                .map(|bag| {
+                    let (g0, g1) = (u16_at(bag[0], 0) as usize, u16_at(bag[1], 0) as usize);
+                    let (m0, m1) = (u16_at(bag[0], 2) as usize, u16_at(bag[1], 2) as usize);
+                    Ok(RawZone {
+                        generators: generators
+                            .get(g0..g1)
+                            .ok_or_else(|| invalid("generator index out of range"))?
+                            .iter()
+                            .map(|g| (u16_at(g, 0) as usize, u16_at(g, 2) as i16 as i32))
+                            // Ranges are two bytes rather than a signed word
+                            .map(|(g, amount)| match g {
+                                KEY_RANGE | VELOCITY_RANGE => (g, amount & 0xffff),
+                                _ => (g, amount),
+                            })
+                            .filter(|&(g, _)| g < GENERATORS)
+                            .collect(),
+                        modulators: modulators
+                            .get(m0..m1)
+                            .ok_or_else(|| invalid("modulator index out of range"))?
+                            .iter()
+                            .map(|m| Modulator::parse(m))
+                            .collect(),
+                    })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// The first zone is global if it doesn't end with the generator that
+// links it to an instrument or a sample
+fn split(zones: &[RawZone], link: usize) -> (RawZone, &[RawZone]) {
+    match zones.first() {
+        Some(first) if first.generators.last().map(|g| g.0) != Some(link) => {
+            (first.clone(), &zones[1..])
+        }
+        _ => (RawZone::default(), zones),
+    }
+}
+
+// An instrument zone with the preset zone that uses it folded in
+#[derive(Clone)]
+pub struct Zone {
+    pub generators: [i32; GENERATORS],
+    pub modulators: Vec<Modulator>,
+    pub samples: Arc<Vec<f64>>,
+    pub sample_rate: f64,
+    // Key the sample plays unchanged at, with its pitch correction
+    pub root: f64,
+    pub loop_points: (usize, usize),
+}
+
+impl Zone {
+    fn matches(&self, key: f64, velocity: f64) -> bool {
+        let (keys, velocities) = (
+            range(self.generators[KEY_RANGE]),
+            range(self.generators[VELOCITY_RANGE]),
+        );
+        (keys.0..=keys.1).contains(&key) && (velocities.0..=velocities.1).contains(&velocity)
+    }
+    fn values(&self, key: f64, velocity: f64) -> [f64; GENERATORS] {
+        let mut values = self.generators.map(|g| g as f64);
+        for modulator in self.modulators.iter() {
+            if let Some((g, value)) = modulator.value(key, velocity) {
+                values[g] += value;
+            }
+        }
+        values
+    }
+}
+
+// A preset of a SoundFont bank, playing every zone whose key and velocity
+// ranges contain the note. The output is mono, so panning is ignored and
+// both halves of stereo samples are mixed. Vibrato, the modulation LFO and
+// the pitch modulation of the modulation envelope aren't supported.
+pub struct SoundFontPreset {
+    pub name: String,
+    pub bank: u16,
+    pub program: u16,
+    sample_rate: f64,
+    parameters: Parameters,
+    zones: Vec<Zone>,
+}
+
+impl SoundFontPreset {
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+}
+
+impl Instrument for SoundFontPreset {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let key = event.key.round();
+        let velocity = (event.velocity * 127.0).round().clamp(1.0, 127.0);
+        let interpolation = match p.value("interpolation") as usize {
+            0 => Interpolation::Nearest,
+            1 => Interpolation::Linear,
+            _ => Interpolation::Cubic,
+        };
+        let layers = self
+            .zones
+            .iter()
+            .filter(|zone| zone.matches(key, velocity))
+            .map(|zone| {
+                let g = &zone.generators;
+                let key = if g[KEY_NUMBER] >= 0 {
+                    g[KEY_NUMBER] as f64
+                } else {
+                    key
+                };
+                let velocity = if g[VELOCITY] >= 0 {
+                    g[VELOCITY] as f64
+                } else {
+                    velocity
+                };
+                let v = zone.values(key, velocity);
+                let pitch = key + event.key - event.key.round();
+                let semitones = (pitch - zone.root) * v[SCALE_TUNING] / 100.0
+                    + v[COARSE_TUNE]
+                    + v[FINE_TUNE] / 100.0
+                    + p.value("tune");
+                let mode = match g[SAMPLE_MODES] & 3 {
+                    1 => LoopMode::Continuous,
+                    3 => LoopMode::Sustain,
+                    _ => LoopMode::NoLoop,
+                };
+                let mut playback = Playback::new(
+                    zone.samples.clone(),
+                    zone.sample_rate,
+                    2f64.powf(semitones / 12.0),
+                )
+                .with_loop(mode, zone.loop_points.0, zone.loop_points.1)
+                .with_gate(event.gate);
+                playback.interpolation = interpolation;
+                let length = playback.length();
+
+                let envelope = |first: usize, hold: f64, decay: f64, sustain: f64| {
+                    let time = |stage: usize| timecents(v[first + stage]);
+                    Envelope::new(
+                        vec![
+                            Stage::linear(time(0), 0.0),
+                            Stage::linear(time(1), 1.0),
+                            Stage::linear(time(2) * hold, 1.0),
+                            Stage::new(time(3) * decay, sustain, DECAY_CURVE),
+                        ],
+                        vec![Stage::new(time(5), 0.0, DECAY_CURVE)],
+                    )
+                };
+                // The decay time is the time a fall to -100 dB would take
+                let attenuation = v[VOL_ENV + 4].clamp(0.0, 1440.0);
+                let volume = envelope(
+                    VOL_ENV,
+                    timecents((60.0 - key) * v[KEY_TO_VOL_HOLD]),
+                    timecents((60.0 - key) * v[KEY_TO_VOL_DECAY]) * (attenuation / 1000.0).min(1.0),
+                    10f64.powf(-attenuation / 200.0),
+                )
+                .gated(event.gate);
+                let gain = 10f64.powf(-v[ATTENUATION].max(0.0) / 200.0) * p.value("gain");
+                let sampler = Gain::new(
+                    AmplitudeModulator::new(event.bent(Box::new(playback)), volume),
+                    gain,
+                );
+
+                let cutoff = v[FILTER_CUTOFF].clamp(1500.0, FILTER_OPEN);
+                let depth = v[MOD_ENV_TO_FILTER];
+                if cutoff >= FILTER_OPEN && depth == 0.0 {
+                    return (1.0, sampler);
+                }
+                let modulation = envelope(
+                    MOD_ENV,
+                    1.0,
+                    1.0,
+                    1.0 - v[MOD_ENV + 4].clamp(0.0, 1000.0) / 1000.0,
+                )
+                .gated(event.gate);
+                let sample_rate = self.sample_rate;
+                let hz = |t: f64| {
+                    let cents = cutoff + depth * modulation.sample(t);
+                    (8.176 * timecents(cents)).min(sample_rate * 0.45)
+                };
+                // Resonance in centibels above the DC gain
+                let q = 10f64.powf((v[FILTER_Q].max(0.0) / 10.0 - 3.01) / 20.0);
+                let mut filter = Biquad::lowpass(sample_rate, hz(0.0), q);
+                filter.set_smoothing(CONTROL_BLOCK as f64 / sample_rate);
+                let end = length.min(event.gate + timecents(v[VOL_ENV + 5]));
+                let samples = (0..(end * sample_rate).ceil() as usize)
+                    .map(|i| {
+                        let t = i as f64 / sample_rate;
+                        if i % CONTROL_BLOCK == 0 {
+                            filter.set_cutoff(hz(t));
+                        }
+                        filter.apply(sampler.sample(t))
+                    })
+                    .collect();
+                (
+                    1.0,
+                    Box::new(Record {
+                        sample_rate,
+                        samples,
+                    }) as DynSampler,
+                )
+            })
+            .collect();
+        Compound::new(layers)
+    }
+    fn release(&self) -> f64 {
+        self.zones
+            .iter()
+            .map(|zone| timecents(zone.generators[VOL_ENV + 5] as f64))
+            .fold(0.0, f64::max)
+    }
+    // Exclusive classes silence each other like hi-hats
+    fn choke_group(&self, event: &NoteEvent) -> Option<usize> {
+        let velocity = (event.velocity * 127.0).round().clamp(1.0, 127.0);
+        self.zones
+            .iter()
+            .find(|zone| zone.matches(event.key.round(), velocity))
+            .map(|zone| zone.generators[EXCLUSIVE_CLASS])
+            .filter(|&class| class > 0)
+            .map(|class| class as usize)
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+// A SoundFont 2 bank, every preset of which is an instrument. Filters are
+// rendered at `sample_rate`.
+pub struct SoundFont {
+    pub name: String,
+    pub presets: Vec<SoundFontPreset>,
+}
+
+impl SoundFont {
+    pub fn parse(data: &[u8], sample_rate: f64) -> io::Result<Self> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"sfbk" {
+            return Err(invalid("not a SoundFont file"));
+        }
+        let mut lists = HashMap::new();
+        for (id, body) in chunks(&data[12..])? {
+            if id == b"LIST" && body.len() >= 4 {
+                lists.insert(
+                    &body[..4],
+                    chunks(&body[4..])?.into_iter().collect::<HashMap<_, _>>(),
+                );
+            }
+        }
+        let list = |id: &[u8]| {
+            lists
+                .get(id)
+                .ok_or_else(|| invalid(&format!("no {} list", String::from_utf8_lossy(id))))
+        };
+        let info = list(b"INFO")?;
+        let sdta = list(b"sdta")?;
+        let pdta = list(b"pdta")?;
+
+        let smpl = sdta.get(&b"smpl"[..]).copied().unwrap_or_default();
+        // 24 bit samples keep their lowest byte in a separate chunk
+        let sm24 = sdta
+            .get(&b"sm24"[..])
+            .filter(|sm24| sm24.len() >= smpl.len() / 2)
+            .copied();
+        let data: Vec<f64> = smpl
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(i, s)| {
+                let high = i16::from_le_bytes([s[0], s[1]]) as i32;
+                match sm24 {
+                    Some(low) => ((high << 8) | low[i] as i32) as f64 / 8388608.0,
+                    None => high as f64 / 32768.0,
+                }
+            })
+            .collect();
+
+        let phdr = records(pdta, b"phdr", 38)?;
+        let inst = records(pdta, b"inst", 22)?;
+        let shdr = records(pdta, b"shdr", 46)?;
+        let preset_zones = zones(
+            &phdr,
+            24,
+            &records(pdta, b"pbag", 4)?,
+            &records(pdta, b"pgen", 4)?,
+            &records(pdta, b"pmod", 10)?,
+        )?;
+        let instrument_zones = zones(
+            &inst,
+            20,
+            &records(pdta, b"ibag", 4)?,
+            &records(pdta, b"igen", 4)?,
+            &records(pdta, b"imod", 10)?,
+        )?;
+
+        // Every sample is copied out once per set of offsets
+        let mut cache: HashMap<(usize, usize), Arc<Vec<f64>>> = HashMap::new();
+        let mut presets = vec![];
+        for (header, zones) in phdr.iter().zip(preset_zones.iter()) {
+            let (preset_global, preset_zones) = split(zones, INSTRUMENT);
+            let mut resolved = vec![];
+            for preset_zone in preset_zones {
+                let Some(instrument) = preset_zone.get(INSTRUMENT) else {
+                    continue;
+                };
+                let instrument = instrument_zones
+                    .get(instrument as usize)
+                    .ok_or_else(|| invalid("instrument index out of range"))?;
+                let mut offsets = [None; GENERATORS];
+                for zone in [&preset_global, preset_zone] {
+                    for &(g, amount) in zone.generators.iter() {
+                        offsets[g] = Some(amount);
+                    }
+                }
+                let mut preset_modulators = preset_global.modulators.clone();
+                merge(&mut preset_modulators, &preset_zone.modulators);
+
+                let (global, zones) = split(instrument, SAMPLE_ID);
+                for zone in zones {
+                    let Some(sample) = zone.get(SAMPLE_ID) else {
+                        continue;
+                    };
+                    let mut g = defaults();
+                    global.apply(&mut g);
+                    zone.apply(&mut g);
+                    // Ranges of both zones intersect, most other preset
+                    // generators offset the instrument ones
+                    let mut empty = false;
+                    for (i, offset) in offsets.iter().enumerate() {
+                        match (i, offset) {
+                            (KEY_RANGE | VELOCITY_RANGE, Some(offset)) => {
+                                let (a, b) = (range(g[i]), range(*offset));
+                                let (low, high) = (a.0.max(b.0) as i32, a.1.min(b.1) as i32);
+                                empty |= low > high;
+                                g[i] = low | (high << 8);
+                            }
+                            (i, Some(offset)) if !ABSOLUTE.contains(&i) => g[i] += offset,
+                            _ => {}
+                        }
+                    }
+                    if empty {
+                        continue;
+                    }
+                    let mut modulators = DEFAULT_MODULATORS.to_vec();
+                    merge(&mut modulators, &global.modulators);
+                    merge(&mut modulators, &zone.modulators);
+                    modulators.extend(preset_modulators.iter().copied());
+
+                    let header = shdr
+                        .get(sample as usize)
+                        .ok_or_else(|| invalid("sample index out of range"))?;
+                    // ROM samples aren't in the file
+                    if u16_at(header, 44) & 0x8000 != 0 {
+                        continue;
+                    }
+                    let address = |at: usize, fine: usize, coarse: usize| {
+                        (u32_at(header, at) as i64 + g[fine] as i64 + 32768 * g[coarse] as i64)
+                            .clamp(0, data.len() as i64) as usize
+                    };
+                    let start = address(20, START_OFFSET, START_COARSE_OFFSET);
+                    let end = address(24, END_OFFSET, END_COARSE_OFFSET).max(start);
+                    let loop_start = address(28, LOOP_START_OFFSET, LOOP_START_COARSE_OFFSET);
+                    let loop_end = address(32, LOOP_END_OFFSET, LOOP_END_COARSE_OFFSET);
+                    let samples = cache
+                        .entry((start, end))
+                        .or_insert_with(|| Arc::new(data[start..end].to_vec()))
+                        .clone();
+                    let root = match g[ROOT_KEY] {
+                        key if key >= 0 => key as f64,
+                        _ => header[40] as f64,
+                    };
+                    resolved.push(Zone {
+                        generators: g,
+                        modulators,
+                        samples,
+                        sample_rate: u32_at(header, 36).max(1) as f64,
+                        root: root - header[41] as i8 as f64 / 100.0,
+                        loop_points: (
+                            loop_start.saturating_sub(start),
+                            loop_end.saturating_sub(start),
+                        ),
+                    });
+                }
+            }
+            presets.push(SoundFontPreset {
+                name: name(&header[..20]),
+                bank: u16_at(header, 22),
+                program: u16_at(header, 20),
+                sample_rate,
+                parameters: Parameters::new(vec![
+                    Parameter::float("gain", 0.0, 2.0, 1.0),
+                    // In semitones
+                    Parameter::float("tune", -12.0, 12.0, 0.0),
+                    Parameter::choice("interpolation", &["nearest", "linear", "cubic"], 2),
+                ]),
+                zones: resolved,
+            });
+        }
+        Ok(Self {
+            name: info.get(&b"INAM"[..]).map(|n| name(n)).unwrap_or_default(),
+            presets,
+        })
+    }
+    pub fn load(path: &str, sample_rate: f64) -> io::Result<Self> {
+        Self::parse(&std::fs::read(path)?, sample_rate)
+    }
+
+    pub fn preset(&self, bank: u16, program: u16) -> Option<&SoundFontPreset> {
+        self.presets
+            .iter()
+            .find(|p| p.bank == bank && p.program == program)
+    }
+    pub fn find(&self, name: &str) -> Option<&SoundFontPreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((body.len() as u32).to_le_bytes());
+        chunk.extend(body);
+        if body.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn list(kind: &[u8], chunks: &[Vec<u8>]) -> Vec<u8> {
+        chunk(b"LIST", &[kind.to_vec(), chunks.concat()].concat())
+    }
+
+    fn words(values: &[u16]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    fn header(name: &str, words_after: &[u16], size: usize) -> Vec<u8> {
+        let mut record = name.as_bytes().to_vec();
+        record.resize(20, 0);
+        record.extend(words(words_after));
+        record.resize(size, 0);
+        record
+    }
+
+    // Generators as (generator, amount), every zone ends where the next
+    // starts
+    fn zone_chunks(zones: &[Vec<(usize, u16)>]) -> (Vec<u8>, Vec<u8>) {
+        let mut bags = vec![];
+        let mut generators = vec![];
+        for zone in zones {
+            bags.extend(words(&[(generators.len() / 4) as u16, 0]));
+            for &(g, amount) in zone {
+                generators.extend(words(&[g as u16, amount]));
+            }
+        }
+        bags.extend(words(&[(generators.len() / 4) as u16, 0]));
+        generators.extend(words(&[0, 0]));
+        (bags, generators)
+    }
+
+    fn sample_header(start: u32, end: u32, root: u8) -> Vec<u8> {
+        let mut record = b"sine".to_vec();
+        record.resize(20, 0);
+        for value in [start, end, start, end, 22050] {
+            record.extend(value.to_le_bytes());
+        }
+        record.extend([root, 0]);
+        record.extend(words(&[0, 1]));
+        record
+    }
+
+    fn keys(low: u16, high: u16) -> u16 {
+        low | (high << 8)
+    }
+
+    // One preset with a global zone over one instrument with a global zone
+    fn soundfont(
+        preset_zones: &[Vec<(usize, u16)>],
+        instrument_zones: &[Vec<(usize, u16)>],
+        sdta: &[Vec<u8>],
+    ) -> Vec<u8> {
+        let (pbag, pgen) = zone_chunks(preset_zones);
+        let (ibag, igen) = zone_chunks(instrument_zones);
+        let phdr = [
+            header("Piano", &[1, 0, 0], 38),
+            header("EOP", &[0, 0, preset_zones.len() as u16], 38),
+        ]
+        .concat();
+        let inst = [
+            header("Strings", &[0], 22),
+            header("EOI", &[instrument_zones.len() as u16], 22),
+        ]
+        .concat();
+        let shdr = [sample_header(0, 4, 60), sample_header(0, 0, 0)].concat();
+        let body = [
+            b"sfbk".to_vec(),
+            list(b"INFO", &[chunk(b"INAM", b"Test\0")]),
+            list(b"sdta", sdta),
+            list(
+                b"pdta",
+                &[
+                    chunk(b"phdr", &phdr),
+                    chunk(b"pbag", &pbag),
+                    chunk(b"pmod", &[0; 10]),
+                    chunk(b"pgen", &pgen),
+                    chunk(b"inst", &inst),
+                    chunk(b"ibag", &ibag),
+                    chunk(b"imod", &[0; 10]),
+                    chunk(b"igen", &igen),
+                    chunk(b"shdr", &shdr),
+                ],
+            ),
+        ]
+        .concat();
+        chunk(b"RIFF", &body)
+    }
+
+    fn smpl() -> Vec<u8> {
+        chunk(b"smpl", &words(&[0x4000, 0xc000, 0, 0x2000]))
+    }
+
+    #[test]
+    fn folds_global_and_preset_zones_into_instrument_zones() {
+        let data = soundfont(
+            &[
+                vec![(VELOCITY_RANGE, keys(0, 100)), (FINE_TUNE, 10)],
+                vec![(KEY_RANGE, keys(60, 72)), (INSTRUMENT, 0)],
+            ],
+            &[
+                vec![(ATTENUATION, 100)],
+                vec![(KEY_RANGE, keys(50, 65)), (SAMPLE_ID, 0)],
+                vec![(KEY_RANGE, keys(80, 90)), (SAMPLE_ID, 0)],
+                vec![(KEY_RANGE, keys(60, 61)), (FINE_TUNE, 5), (SAMPLE_ID, 0)],
+            ],
+            &[smpl()],
+        );
+        let font = SoundFont::parse(&data, 44100.0).unwrap();
+        assert_eq!(font.name, "Test");
+        assert_eq!(font.presets.len(), 1);
+        let preset = font.preset(0, 1).unwrap();
+        assert_eq!(preset.name, "Piano");
+        // The second zone doesn't overlap the preset's keys
+        let zones = preset.zones();
+        assert_eq!(zones.len(), 2);
+        let g = &zones[0].generators;
+        assert_eq!(range(g[KEY_RANGE]), (60.0, 65.0));
+        assert_eq!(range(g[VELOCITY_RANGE]), (0.0, 100.0));
+        assert_eq!(g[ATTENUATION], 100);
+        assert_eq!(g[FINE_TUNE], 10);
+        assert_eq!(zones[1].generators[FINE_TUNE], 15);
+        assert_eq!(range(zones[1].generators[KEY_RANGE]), (60.0, 61.0));
+        assert_eq!(zones[0].root, 60.0);
+        assert_eq!(zones[0].sample_rate, 22050.0);
+        assert_eq!(*zones[0].samples, vec![0.5, -0.5, 0.0, 0.25]);
+    }
+
+    #[test]
+    fn reads_the_low_byte_of_24_bit_samples() {
+        let data = soundfont(
+            &[vec![(INSTRUMENT, 0)]],
+            &[vec![(SAMPLE_ID, 0)]],
+            &[smpl(), chunk(b"sm24", &[0x80, 0, 0, 0])],
+        );
+        let font = SoundFont::parse(&data, 44100.0).unwrap();
+        let samples = &font.presets[0].zones()[0].samples;
+        assert_eq!(samples[0], 0.5 + 128.0 / 8388608.0);
+        assert_eq!(samples[1], -0.5);
+    }
+
+    #[test]
+    fn rejects_indices_out_of_range() {
+        let error = |preset: Vec<(usize, u16)>, instrument: Vec<(usize, u16)>| {
+            let data = soundfont(&[preset], &[instrument], &[smpl()]);
+            SoundFont::parse(&data, 44100.0).err().unwrap().to_string()
+        };
+        assert_eq!(
+            error(vec![(INSTRUMENT, 3)], vec![(SAMPLE_ID, 0)]),
+            "instrument index out of range"
+        );
+        assert_eq!(
+            error(vec![(INSTRUMENT, 0)], vec![(SAMPLE_ID, 7)]),
+            "sample index out of range"
+        );
+
+        // A bag pointing past the generators
+        let mut data = soundfont(&[vec![(INSTRUMENT, 0)]], &[vec![(SAMPLE_ID, 0)]], &[smpl()]);
+        let at = data.windows(4).position(|w| w == b"ibag").unwrap() + 8 + 4;
+        data[at..at + 2].copy_from_slice(&9u16.to_le_bytes());
+        let error = SoundFont::parse(&data, 44100.0).err().unwrap();
+        assert_eq!(error.to_string(), "generator index out of range");
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let data = soundfont(&[vec![(INSTRUMENT, 0)]], &[vec![(SAMPLE_ID, 0)]], &[smpl()]);
+        assert!(SoundFont::parse(&data, 44100.0).is_ok());
+        let error = SoundFont::parse(&data[..data.len() - 10], 44100.0)
+            .err()
+            .unwrap();
+        assert_eq!(error.to_string(), "truncated SoundFont chunk");
+        assert!(SoundFont::parse(&data[..11], 44100.0).is_err());
+    }
+}
@@ -0,0 +1,146 @@
+use super::*;
+use crate::filter::{Biquad, BiquadKind, Filter};
+
+const WAVEFORMS: [&str; 4] = ["sine", "triangle", "square", "sawtooth"];
+const FILTERS: [&str; 3] = ["lowpass", "bandpass", "highpass"];
+// Samples between filter cutoff updates
+const CONTROL_BLOCK: usize = 16;
+
+// Classic analog style voice: three detuned oscillators and noise through a
+// resonant filter with its own envelope. Filters keep state, so every note is
+// rendered into a `Record` at `sample_rate`.
+pub struct Subtractive {
+    sample_rate: f64,
+    parameters: Parameters,
+}
+
+impl Subtractive {
+    pub fn new(sample_rate: f64) -> Self {
+        let oscillator = |n: usize, wave: usize, level: f64, octave: i64, detune: f64| {
+            vec![
+                Parameter::choice(&format!("osc{}_wave", n), &WAVEFORMS, wave),
+                Parameter::float(&format!("osc{}_level", n), 0.0, 1.0, level),
+                Parameter::integer(&format!("osc{}_octave", n), -3, 3, octave),
+                Parameter::float(&format!("osc{}_detune", n), -100.0, 100.0, detune),
+            ]
+        };
+        let mut parameters = vec![];
+        parameters.extend(oscillator(1, 3, 1.0, 0, 0.0));
+        parameters.extend(oscillator(2, 3, 0.7, 0, 7.0));
+        parameters.extend(oscillator(3, 2, 0.0, -1, 0.0));
+        parameters.extend(vec![
+            Parameter::float("pulse_width", 0.05, 0.95, 0.5),
+            Parameter::float("noise", 0.0, 1.0, 0.0),
+            Parameter::choice("filter", &FILTERS, 0),
+            Parameter::float("cutoff", 20.0, 20000.0, 800.0),
+            Parameter::float("resonance", 0.5, 20.0, 2.0),
+            // 1 follows the keyboard exactly, relative to middle C
+            Parameter::float("key_tracking", 0.0, 1.0, 0.5),
+            // In octaves
+            Parameter::float("filter_envelope", -8.0, 8.0, 3.0),
+            Parameter::float("filter_velocity", 0.0, 4.0, 1.0),
+            Parameter::float("filter_attack", 0.0, 5.0, 0.01),
+            Parameter::float("filter_decay", 0.0, 5.0, 0.3),
+            Parameter::float("filter_sustain", 0.0, 1.0, 0.2),
+            Parameter::float("filter_release", 0.0, 5.0, 0.3),
+            Parameter::float("attack", 0.0, 5.0, 0.01),
+            Parameter::float("decay", 0.0, 5.0, 0.2),
+            Parameter::float("sustain", 0.0, 1.0, 0.7),
+            Parameter::float("release", 0.0, 5.0, 0.2),
+            Parameter::float("gain", 0.0, 2.0, 0.3),
+        ]);
+        parameters.extend(Glide::parameters());
+        Self {
+            sample_rate,
+            parameters: Parameters::new(parameters),
+        }
+    }
+
+    fn waveform(&self, index: f64) -> Waveform {
+        match index as usize {
+            0 => Waveform::Sine,
+            1 => Waveform::Triangle,
+            2 => Waveform::Square(self.parameters.value("pulse_width")),
+            _ => Waveform::Sawtooth,
+        }
+    }
+}
+
+impl Instrument for Subtractive {
+    fn name(&self) -> &str {
+        "subtractive"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let oscillators: Vec<(Waveform, f64, f64)> = (1..=3)
+            .map(|n| {
+                let value = |name: &str| p.value(&format!("osc{}_{}", n, name));
+                let ratio = 2f64.powf(value("octave") + value("detune") / 1200.0);
+                (self.waveform(value("wave")), value("level"), ratio)
+            })
+            .filter(|&(_, level, _)| level > 0.0)
+            .collect();
+        let noise = Noise::new(sample_rate, event.seed());
+        let frequency = event.frequency_curve();
+        let amplitude = Envelope::adsr(
+            p.value("attack"),
+            p.value("decay"),
+            p.value("sustain"),
+            p.value("release"),
+        )
+        .gated(event.gate);
+        let envelope = Envelope::adsr(
+            p.value("filter_attack"),
+            p.value("filter_decay"),
+            p.value("filter_sustain"),
+            p.value("filter_release"),
+        )
+        .gated(event.gate);
+
+        // Everything but the envelope is fixed for the whole note
+        let octaves = p.value("key_tracking") * (event.key - 60.0) / 12.0
+            + p.value("filter_velocity") * (event.velocity - 1.0);
+        let base = p.value("cutoff") * 2f64.powf(octaves);
+        let cutoff = |t: f64| base * 2f64.powf(p.value("filter_envelope") * envelope.sample(t));
+        let kind = match p.value("filter") as usize {
+            0 => BiquadKind::LowPass,
+            1 => BiquadKind::BandPass,
+            _ => BiquadKind::HighPass,
+        };
+        let mut filter = Biquad::new(kind, sample_rate, cutoff(0.0), p.value("resonance"), 0.0);
+        filter.set_smoothing(CONTROL_BLOCK as f64 / sample_rate);
+
+        let gain = p.value("gain") * event.velocity;
+        let noise_level = p.value("noise");
+        let length = ((event.gate + p.value("release")) * sample_rate).ceil() as usize;
+        let mut phases = vec![0.0; oscillators.len()];
+        let mut samples = Vec::with_capacity(length);
+        for i in 0..length {
+            let t = i as f64 / sample_rate;
+            if i % CONTROL_BLOCK == 0 {
+                filter.set_cutoff(cutoff(t));
+            }
+            let step = frequency.sample(t) / sample_rate;
+            let mut value = noise.sample(t) * noise_level;
+            for ((waveform, level, ratio), phase) in oscillators.iter().zip(phases.iter_mut()) {
+                value += level * waveform.band_limited(*phase, step * ratio);
+                *phase = (*phase + step * ratio).fract();
+            }
+            samples.push(filter.apply(value) * amplitude.sample(t) * gain);
+        }
+        Box::new(Record {
+            sample_rate,
+            samples,
+        })
+    }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
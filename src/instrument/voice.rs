@@ -0,0 +1,229 @@
+use super::*;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VoiceMode {
+    Poly,
+    // One voice, every note retriggers
+    Mono,
+    // One voice, notes starting while the previous key is held continue
+    // the previous note at the new pitch
+    Legato,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stealing {
+    Oldest,
+    // Released voices first, then the lowest velocity
+    Quietest,
+    // A new note takes over the voice already playing its key, otherwise
+    // the oldest voice
+    SameNote,
+}
+
+// A note as it ends up on a voice
+#[derive(Clone)]
+pub struct Voice {
+    pub event: NoteEvent,
+    // When the voice was stolen, relative to the start of the note
+    pub cut: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VoiceAllocator {
+    pub polyphony: usize,
+    pub mode: VoiceMode,
+    pub stealing: Stealing,
+    // Fade out of a stolen voice
+    pub steal_fade: f64,
+    pub glide: Option<Glide>,
+}
+
+impl Default for VoiceAllocator {
+    fn default() -> Self {
+        Self::new(32)
+    }
+}
+
+impl VoiceAllocator {
+    pub fn new(polyphony: usize) -> Self {
+        Self {
+            polyphony: polyphony.max(1),
+            mode: VoiceMode::Poly,
+            stealing: Stealing::Oldest,
+            steal_fade: 0.005,
+            glide: None,
+        }
+    }
+    pub fn mono() -> Self {
+        Self {
+            mode: VoiceMode::Mono,
+            ..Self::new(1)
+        }
+    }
+    pub fn legato() -> Self {
+        Self {
+            mode: VoiceMode::Legato,
+            ..Self::new(1)
+        }
+    }
+    pub fn with_stealing(mut self, stealing: Stealing) -> Self {
+        self.stealing = stealing;
+        self
+    }
+    pub fn with_glide(mut self, glide: Glide) -> Self {
+        self.glide = Some(glide);
+        self
+    }
+
+    // Channels are allocated independently
+    pub fn allocate(&self, events: &[NoteEvent], instrument: &dyn Instrument) -> Vec<Voice> {
+        let mut channels: Vec<usize> = events.iter().map(|e| e.channel).collect();
+        channels.sort_unstable();
+        channels.dedup();
+        let mut voices = vec![];
+        for channel in channels {
+            let mut notes: Vec<NoteEvent> = events
+                .iter()
+                .filter(|e| e.channel == channel)
+                .cloned()
+                .collect();
+            notes.sort_by(|a, b| a.time.total_cmp(&b.time));
+            notes = self.glide_notes(notes);
+            count_round_robins(&mut notes);
+            voices.extend(self.assign(notes, instrument));
+        }
+        voices
+    }
+
+    // Legato joins and slides from the previous note of the channel
+    fn glide_notes(&self, notes: Vec<NoteEvent>) -> Vec<NoteEvent> {
+        let glide = self.glide.unwrap_or(Glide::constant(0.0));
+        let mut result: Vec<NoteEvent> = vec![];
+        for note in notes {
+            let Some(last) = result.last_mut() else {
+                result.push(note);
+                continue;
+            };
+            let held = note.time <= last.end() + 1e-9;
+            if self.mode == VoiceMode::Legato && held {
+                *last = glide.join(last, &note);
+            } else if self.glide.is_some() && (held || !glide.legato) {
+                let from = last.key + bend_at(last, last.gate) - note.key;
+                result.push(glide.slide(note, from));
+            } else {
+                result.push(note);
+            }
+        }
+        result
+    }
+
+    fn assign(&self, notes: Vec<NoteEvent>, instrument: &dyn Instrument) -> Vec<Voice> {
+        let release = instrument.release();
+        let mut voices: Vec<Voice> = vec![];
+        // Index into `voices` of the note every voice is playing
+        let mut playing: Vec<Option<usize>> = vec![None; self.polyphony];
+        for mut note in notes {
+            let t = note.time;
+            let busy = |voice: &Voice| {
+                let end = match voice.cut {
+                    Some(cut) => voice.event.time + cut + self.steal_fade,
+                    None => voice.event.end() + release,
+                };
+                end > t
+            };
+            for slot in playing.iter_mut() {
+                if slot.is_some_and(|i| !busy(&voices[i])) {
+                    *slot = None;
+                }
+            }
+            // Notes of the same choke group silence each other
+            if let Some(group) = instrument.choke_group(&note) {
+                for slot in playing.iter_mut() {
+                    if let Some(i) = *slot {
+                        let voice = &mut voices[i];
+                        if instrument.choke_group(&voice.event) == Some(group) {
+                            voice.cut = Some(t - voice.event.time);
+                            *slot = None;
+                        }
+                    }
+                }
+            }
+            let same = playing
+                .iter()
+                .position(|slot| slot.is_some_and(|i| voices[i].event.key == note.key));
+            let slot = match (self.stealing, same) {
+                (Stealing::SameNote, Some(slot)) => slot,
+                _ => match playing.iter().position(|slot| slot.is_none()) {
+                    Some(slot) => slot,
+                    None => self.victim(&playing, &voices, t),
+                },
+            };
+            if let Some(i) = playing[slot] {
+                let voice = &mut voices[i];
+                voice.cut = Some(t - voice.event.time);
+            }
+            note.voice = slot;
+            playing[slot] = Some(voices.len());
+            voices.push(Voice {
+                event: note,
+                cut: None,
+            });
+        }
+        voices
+    }
+
+    fn victim(&self, playing: &[Option<usize>], voices: &[Voice], t: f64) -> usize {
+        let event = |slot: usize| &voices[playing[slot].unwrap()].event;
+        let slots = 0..playing.len();
+        match self.stealing {
+            Stealing::Quietest => slots
+                .min_by(|&a, &b| {
+                    let (a, b) = (event(a), event(b));
+                    let held = |e: &NoteEvent| e.end() > t;
+                    held(a)
+                        .cmp(&held(b))
+                        .then(a.velocity.total_cmp(&b.velocity))
+                        .then(a.time.total_cmp(&b.time))
+                })
+                .unwrap(),
+            Stealing::Oldest | Stealing::SameNote => slots
+                .min_by(|&a, &b| event(a).time.total_cmp(&event(b).time))
+                .unwrap(),
+        }
+    }
+
+    pub fn render(&self, instrument: &dyn Instrument, events: &[NoteEvent]) -> DynSampler {
+        Compound::play(
+            self.allocate(events, instrument)
+                .into_iter()
+                .map(|voice| {
+                    let sampler = instrument.play(&voice.event);
+                    let sampler = match voice.cut {
+                        Some(cut) => Fade::new(
+                            sampler,
+                            -self.steal_fade,
+                            cut + self.steal_fade / 2.0,
+                            self.steal_fade,
+                            FadeCurve::RaisedCosine,
+                        ),
+                        None => sampler,
+                    };
+                    (voice.event.time, sampler)
+                })
+                .collect(),
+        )
+    }
+}
+
+// Numbers the notes of one channel, sorted by time, by how many notes of the
+// same key came before them. Legato notes joined to the previous one aren't
+// counted.
+fn count_round_robins(notes: &mut [NoteEvent]) {
+    let mut played: HashMap<i64, usize> = HashMap::new();
+    for note in notes.iter_mut() {
+        let count = played.entry(note.key.round() as i64).or_insert(0);
+        note.round_robin = *count;
+        *count += 1;
+    }
+}
@@ -0,0 +1,294 @@
+use super::*;
+use crate::filter::{Biquad, BiquadKind, DelayLine, Filter, Interpolation};
+
+// Room in the delay lines for notes bent below their key
+const LOWEST: f64 = 20.0;
+
+// Samples `delay` behind, counting the sample written right after reading
+fn tap(line: &DelayLine, delay: f64) -> f64 {
+    line.read(delay - 1.0, Interpolation::Linear)
+}
+
+fn render(sample_rate: f64, duration: f64, mut tick: impl FnMut(usize, f64) -> f64) -> DynSampler {
+    let length = (duration * sample_rate).ceil() as usize;
+    let samples = (0..length)
+        .map(|i| tick(i, i as f64 / sample_rate))
+        .collect();
+    Box::new(Record {
+        sample_rate,
+        samples,
+    })
+}
+
+// Karplus-Strong plucked string: a noise burst shaped by the pick position
+// circulating through a delay line with a loss filter. The fractional part
+// of the loop length is tuned with a first order all-pass.
+pub struct PluckedString {
+    sample_rate: f64,
+    parameters: Parameters,
+}
+
+impl PluckedString {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            parameters: Parameters::new(
+                [
+                    vec![
+                        // Seconds to decay by 60 dB while held
+                        Parameter::float("decay", 0.1, 20.0, 3.0),
+                        Parameter::float("damping", 0.0, 1.0, 0.5),
+                        // Distance of the pick from the bridge, relative to the string
+                        Parameter::float("pick_position", 0.01, 0.5, 0.13),
+                        Parameter::float("brightness", 0.0, 1.0, 0.7),
+                        // Seconds to decay by 60 dB after the gate
+                        Parameter::float("release", 0.01, 5.0, 0.1),
+                        Parameter::float("gain", 0.0, 2.0, 0.5),
+                    ],
+                    Glide::parameters(),
+                ]
+                .concat(),
+            ),
+        }
+    }
+}
+
+impl Instrument for PluckedString {
+    fn name(&self) -> &str {
+        "plucked"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let frequency = event.frequency_curve();
+        let period = sample_rate / event.frequency;
+        let stretch = p.value("damping") / 2.0;
+
+        // Lowpassed noise burst, louder and brighter with velocity, minus a
+        // copy delayed by the pick position which notches its harmonics
+        let noise = Noise::new(sample_rate, event.seed());
+        let smoothing = 1.0 - p.value("brightness") * (0.5 + event.velocity / 2.0);
+        let mut burst = vec![0.0; period.ceil() as usize];
+        let mut last = 0.0;
+        for (i, value) in burst.iter_mut().enumerate() {
+            last += (noise.sample(i as f64 / sample_rate) - last) * (1.0 - smoothing.min(0.99));
+            *value = last * event.velocity;
+        }
+        let pick = ((p.value("pick_position") * period).round() as usize).max(1);
+        let excitation = |i: usize| {
+            let at = |i: usize| burst.get(i).cloned().unwrap_or(0.0);
+            at(i) - i.checked_sub(pick).map_or(0.0, at)
+        };
+
+        let mut line = DelayLine::new((sample_rate / LOWEST) as usize);
+        let (mut x1, mut y1, mut previous) = (0.0, 0.0, 0.0);
+        let (decay, release) = (p.value("decay"), p.value("release"));
+        let gain = p.value("gain");
+        render(sample_rate, event.gate + release, |i, t| {
+            let period = sample_rate / frequency.sample(t);
+            // Loop delay is the line, the all-pass and the loss filter
+            let length = period - stretch;
+            let whole = (length - 0.1).floor().max(1.0);
+            let fraction = length - whole;
+            let c = (1.0 - fraction) / (1.0 + fraction);
+            let delayed = line.read(whole - 1.0, Interpolation::Nearest);
+            let tuned = c * delayed + x1 - c * y1;
+            x1 = delayed;
+            y1 = tuned;
+            let t60 = if t < event.gate { decay } else { release };
+            let loss = 10f64.powf(-3.0 * period / (sample_rate * t60));
+            let y = excitation(i) + loss * ((1.0 - stretch) * tuned + stretch * previous);
+            previous = tuned;
+            line.write(y);
+            y * gain
+        })
+    }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+// Bowed string after the STK model: the bow sits between a neck and a
+// bridge delay line and sticks or slips depending on the velocity
+// difference. Note pressure adds to the bow pressure.
+pub struct BowedString {
+    sample_rate: f64,
+    parameters: Parameters,
+}
+
+impl BowedString {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            parameters: Parameters::new(
+                [
+                    vec![
+                        Parameter::float("pressure", 0.0, 1.0, 0.5),
+                        // Distance of the bow from the bridge, relative to the string
+                        Parameter::float("position", 0.02, 0.5, 0.18),
+                        Parameter::float("attack", 0.0, 2.0, 0.05),
+                        Parameter::float("release", 0.0, 2.0, 0.1),
+                        Parameter::float("gain", 0.0, 4.0, 1.0),
+                    ],
+                    Glide::parameters(),
+                ]
+                .concat(),
+            ),
+        }
+    }
+}
+
+impl Instrument for BowedString {
+    fn name(&self) -> &str {
+        "bowed"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let frequency = event.frequency_curve();
+        let pressure = event.pressure_curve();
+        let bow = Envelope::adsr(p.value("attack"), 0.0, 1.0, p.value("release")).gated(event.gate);
+        let max_velocity = 0.03 + 0.2 * event.velocity;
+        let position = p.value("position");
+
+        let mut neck = DelayLine::new((sample_rate / LOWEST) as usize);
+        let mut bridge = DelayLine::new((sample_rate / LOWEST) as usize);
+        let pole = 0.75 - 0.2 * 22050.0 / sample_rate;
+        let mut string = 0.0;
+        // Two broad body resonances
+        let mut body = [
+            Biquad::new(BiquadKind::Peaking, sample_rate, 280.0, 1.5, 6.0),
+            Biquad::new(BiquadKind::Peaking, sample_rate, 460.0, 1.5, 6.0),
+        ];
+        let gain = p.value("gain");
+        render(sample_rate, event.gate + p.value("release"), |_, t| {
+            // The string filter delays by about pole / (1 - pole) samples
+            let length = sample_rate / frequency.sample(t) - pole / (1.0 - pole);
+            let slope = 5.0 - 4.0 * (p.value("pressure") + pressure.sample(t)).clamp(0.0, 1.0);
+            let bridge_out = tap(&bridge, length * position);
+            let neck_out = tap(&neck, length * (1.0 - position));
+            string = 0.95 * (1.0 - pole) * bridge_out + pole * string;
+            let (bridge_reflection, nut_reflection) = (-string, -neck_out);
+            let difference = bow.sample(t) * max_velocity - (bridge_reflection + nut_reflection);
+            let friction = (difference * slope + 0.001).abs() + 0.75;
+            let velocity = difference * friction.powi(-4).min(1.0);
+            neck.write(bridge_reflection + velocity);
+            bridge.write(nut_reflection + velocity);
+            let out = body.iter_mut().fold(bridge_out, |x, f| f.apply(x));
+            out * 0.1248 * gain
+        })
+    }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+// Blown pipe after the STK flute: breath noise excites a jet delay whose
+// non-linearity drives the bore delay. Note pressure adds to the breath.
+pub struct BlownPipe {
+    sample_rate: f64,
+    parameters: Parameters,
+}
+
+impl BlownPipe {
+    pub fn new(sample_rate: f64) -> Self {
+        Self {
+            sample_rate,
+            parameters: Parameters::new(
+                [
+                    vec![
+                        Parameter::float("breath", 0.0, 1.0, 0.15),
+                        // Length of the jet relative to the bore, shorter jets make
+                        // low notes overblow to the octave
+                        Parameter::float("jet_ratio", 0.05, 1.0, 0.55),
+                        Parameter::float("attack", 0.0, 2.0, 0.05),
+                        Parameter::float("release", 0.0, 2.0, 0.1),
+                        Parameter::float("gain", 0.0, 4.0, 1.0),
+                    ],
+                    Glide::parameters(),
+                ]
+                .concat(),
+            ),
+        }
+    }
+}
+
+impl Instrument for BlownPipe {
+    fn name(&self) -> &str {
+        "pipe"
+    }
+    fn play(&self, event: &NoteEvent) -> DynSampler {
+        let p = &self.parameters;
+        let sample_rate = self.sample_rate;
+        let frequency = event.frequency_curve();
+        let pressure = event.pressure_curve();
+        let noise = Noise::new(sample_rate, event.seed());
+        let envelope =
+            Envelope::adsr(p.value("attack"), 0.0, 1.0, p.value("release")).gated(event.gate);
+        let max_pressure = 0.9 + 0.2 * event.velocity;
+        let (breath, jet_ratio) = (p.value("breath"), p.value("jet_ratio"));
+
+        let mut jet = DelayLine::new((sample_rate / LOWEST) as usize);
+        let mut bore = DelayLine::new((sample_rate / LOWEST) as usize);
+        let pole = 0.7 - 0.1 * 22050.0 / sample_rate;
+        let (mut loss, mut dc_in, mut dc_out) = (0.0, 0.0, 0.0);
+        let gain = p.value("gain");
+        render(sample_rate, event.gate + p.value("release"), |_, t| {
+            // The jet path lengthens the loop by about 2%
+            let length = sample_rate / frequency.sample(t) / 1.022 - 1.0;
+            let level = envelope.sample(t);
+            let blow =
+                (max_pressure + pressure.sample(t)) * level + noise.sample(t) * breath * level;
+            let bore_out = tap(&bore, length);
+            loss = (1.0 - pole) * bore_out + pole * loss;
+            dc_out = loss - dc_in + 0.99 * dc_out;
+            dc_in = loss;
+            jet.write(blow - 0.5 * dc_out);
+            let x = tap(&jet, length * jet_ratio + 1.0);
+            let jet_out = (x * (x * x - 1.0)).clamp(-1.0, 1.0);
+            bore.write(jet_out + 0.5 * dc_out);
+            0.3 * bore_out * gain
+        })
+    }
+    fn release(&self) -> f64 {
+        self.parameters.value("release")
+    }
+    fn parameters(&self) -> &Parameters {
+        &self.parameters
+    }
+    fn parameters_mut(&mut self) -> &mut Parameters {
+        &mut self.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notes_are_silent_before_they_start() {
+        let instruments: [Box<dyn Instrument>; 3] = [
+            Box::new(PluckedString::new(44100.0)),
+            Box::new(BowedString::new(44100.0)),
+            Box::new(BlownPipe::new(44100.0)),
+        ];
+        for instrument in instruments.iter() {
+            let event = NoteEvent::new(57.0, 1.0, 0.2).at(1.0);
+            let song = instrument.voices().render(instrument.as_ref(), &[event]);
+            assert!((0..44100).all(|i| song.sample(i as f64 / 44100.0) == 0.0));
+            assert!((44100..48000).any(|i| song.sample(i as f64 / 44100.0) != 0.0));
+        }
+    }
+}
@@ -4,3 +4,4 @@ pub mod instrument;
 pub mod mml;
 pub mod notes;
 pub mod sampler;
+pub mod tempo;
@@ -33,7 +33,7 @@ impl Player for StdoutPlayer {
 
 fn main() {
     StdoutPlayer::play(
-        mml::play::<LegitInstrument>(mml::SMOKE_ON_THE_WATER),
+        mml::play(&LegitInstrument::new(), mml::SMOKE_ON_THE_WATER),
         SAMPLE_RATE,
         100.0,
     );
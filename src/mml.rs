@@ -1,8 +1,8 @@
-use crate::instrument::Instrument;
-use crate::notes::*;
+use crate::instrument::{Glide, Instrument, NoteEvent};
+use crate::notes::semitone;
 use crate::sampler::*;
+use crate::tempo::{note_length, TempoMap};
 use regex::Regex;
-use std::collections::HashMap;
 
 pub const AIR_ON_G_STRING: &'static str = "t33>e1&e8a16f16e32d32c16<b16>c16<b4a16g8.>g2&g16e16<a+16a16>d16c+16g16f16f2&f16d16<a16g16>c16<b16>f16e16e4.f+16g16c8c32d32e8d16d16c16<b16a16a32b32>c8.<b16a16g2>e1&e8a16f16e32d32c16<b16>c16<b4a16g8.>g2&g16e16<a+16a16>d16c+16g16f16f2&f16d16<a16g16>c16<b16>f16e16e4.f+16g16c8c32d32e8d16d16c16<b16a16a32b32>c8.<b16a16g4.&g16,<c8>c8<b8<b8a8>a8g8<g8f8>f8f+8<f+8g8>g8f8<f8e8>e8d8<d8c+8>c+8<a8>a8<d8>d8c8<c8<b8>b8g8>g8c8>c8<b8<b8a8>a8f+8d8g8c8d8<d8g16a16b16>c16d16f16e16d16c8>c8<b8<b8a8>a8g8<g8f8>f8f+8<f+8g8>g8f8<f8e8>e8d8<d8c+8>c+8<a8>a8<d8>d8c8<c8<b8>b8g8>g8c8>c8<b8<b8a8>a8f+8d8g8c8d8<d8g4.&g16";
 
@@ -14,42 +14,28 @@ pub const SMOKE_ON_THE_WATER:&'static str = "v127l8t112gra#r>c4<rgra#r>c#c4<r4gr
 
 pub const CREEP_RADIOHEAD:&'static str = "t93l8r1r1r1r4.d+4r1r1r1r1r1r1r1r1r1r1r1r1r1r4.g4r1f+r1r1r1r1r1r1r1r1r1r1r1r2rd+1&d+1r1r1r1r1r1r1r1r1r1r1r1r1r1r1rg4,r1d<b4b4b>d4r4d+4<b>d+4.e4.<b4r>d+<br1r1r1r1r1r1r1>d+<b4b4.>d+4r1r1r1r1r1d4<b>d4<b>g4r4d+<b>d+f+d+f+bf+d+f+4d+b4r4ececge>c<geg4e>c<grgd+g4gd+crd+4rd+2r1r1r2.d+16e16d+4r1r2re16f16e4r2.rc1&c1r1r1r1r1r1r1r1r1r1r1r1bf+d+<b4>d+<b>d+rcecee4c>c<gec4ec4rd+4rfd+4cgd+4d+2&d+r1r1r2.d+16e16d+4r1r2re16f16e4r1r2rd+16f16d+1&d+r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1d+2.&d+.r16d+4.r1r1b4.a4,o2g4.&g16g16gg4gg4.&g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<b>c4.&c16<b16>cc4<b>c4d4d+4<f4g4.&g16g16gg4gg4.g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<b>c4.&c16<b16>cc4<b>c4d4d+4f4<g4.&g16g16gg4gg4.&g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<b>c4.&c16<b16>cc4<b>c4d4d+4f4<g4.&g16g16gg4gg4.&g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<g>c1<g4.&g16g16gg4gg4.&g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<b>c4.&c16<b16>cc4<b>c4d4d+4<f4g4.&g16g16gg4gg4.g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<b>c4.&c16<b16>cc4<b>c4d4d+4f4<g4.&g16g16gg4gg4.&g16g16gg4gb4.&b16f+16bb4f+b4.&b16f+16bb4b>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<g>c4.&c16<g16>cc4<g>c4d4d+4f4<g4.&g16g16gg4>d<g4.g16g16g16a16g4f+b4.&b16f+16bb4f+b4.f+f+16g+16f+4f>c4.c16<g16>cc4<g>ccccc16d16cc4ccccccccccddd+d+ffg4.&g16g16gg4gg4.g16g16g16a16g4.<b4b.b16bb4.b4b.b16bbb>dc4c.c16ccccc4c.c16cccdc1&c1<g2.&ggg1b2.&bbb1>c2.&ccc1c2.&ccc1<g2.&ggg2.&ggb2.&bbb2.&bb>c2.&ccc2.&ccc1&c1ga4a16b1&b2&b16,t93l8v115r1r1r1r1r1r1r1r2a16a16gf+g4.r1rdaggf+4.r1r.d16agf+g4e4.r1agf+g4.r1rcagf+g4d4c16<b4&b16r2rb16b16>a16a16g4f+2r1r16a16aaga+4g4.r2.rcgaga+4g4.r2.r.g16gb4b4.r1r4gb4b4f+4e16d+4&d+16r2bb16>c.<bb16ab4&b16r1r4gaga+4g2r2.r16c16agf+g4.r1r.d16a16a16ggf+4.r1r.d16a16a16gf+g4e4d16c4.r2rd16a16a16gf+g4.r1rcagf+g4d4c16<b4&b16r2r.>d16aggf+4.r1r4gagb4g4.r2.rggaga+4g2r2.r16g16gb4b4.r1r4gb4b4f+4e16d+4&d+16r2r16bb16>c<bb16ab2.&b16r2.gaga+4g2r4>d4c4d4cr4g2.r4a4gf+r4dd+4&d+16f+.b2f+16e16d+4r2.g2.r4a4gg4.rdd+4r4f4r4g4r4ga1&a4.g1f+4r1r2.a1g2f+2g4r1r.<d16agf+g4d4r1r16a16a16a16g4f+4.r1rdgagb4g4.r2.rggaga+4g2r2.r16g16gb4b4.r1r4gb4b4f+4e16d+4&d+16r2bb16>c.<bb16ab4&b16r1r4gaga+4g2r2.rgagb4g2,t93l8r1r1r1r4.d+4l1rrrrrrrrrrrrrr4l8.g4r1f+l1rrrrrrrrrrrr2l8rd+1&d+l1rrrrrrrrrrrrrrrl8g4,r1d<b4b4b>d4r4d+4<b>d+4.e4.<b4r>d+<br1r1r1r1r1r1r1>d+<b4b4.>d+4r1r1r1r1r1d4<b>d4<b>g4r4d+<b>d+f+d+f+bf+d+f+4d+b4r4ececge>c<geg4e>c<grgd+g4gd+crd+4rd+2r1r1r2.d+16e16d+4r1r2re16f16e4r2.rc1&c1r1r1r1r1r1r1r1r1r1r1r1bf+d+<b4>d+<b>d+rcecee4c>c<gec4ec4rd+4rfd+4cgd+4d+2&d+r1r1r2.d+16e16d+4r1r2re16f16e4r1r2rd+16f16d+1&d+r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1d+2.&d+.r16d+4.r1r1b4.a4,r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1<d2.r1r4f+2.r1r4g2.r1r4g1&g1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1d2.r1r4f+2.r1r4g2.r1r4g2.r1r4ddddddddddddddddf+f+f+f+f+f+f+f+f+f+f+f+f+f+f+f+ggggggggggggggggggggggggggggggggddddddddddddddddf+f+f+f+f+f+f+f+f+f+f+f+f+f+f+f+ggggggggggggggggg1&g1r1r1r1r1r1r1r1r2g4r4g2.&g.r16g4.g4gg4b2.&b.r16b4.r2re2&e.r16e4e4.b4.a4d+2.&d+16r.d+4.,r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1<g2.r1r4b2.r1r4>c2.r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r4<g2.r1r4b2.r1r4>c2.r1r4c2.r1r4<ggggggggggggggggbbbbbbbbbbbbbbbb>cccccccccccccccccccccccccccccccc<ggggggggggggggggbbbbbbbbbbbbbbbb>ccccccccccccccccc1&c1r1r1r1r1r1r1r1r2c4r4<b2.&b.r16>c4.<b4bb4r1r1g2&g.r16a4g4.r2rg2.&g16,r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1<b2.r1r4>d+2.r1r4e2.r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r1r4<b2.r1r4>d+2.r1r4e2.r1r4d+2.r1r4<bbbbbbbbbbbbbbbb>d+d+d+d+d+d+d+d+d+d+d+d+d+d+d+d+eeeeeeeeeeeeeeeed+d+d+d+d+d+d+d+d+d+d+d+d+d+d+d+<bbbbbbbbbbbbbbb>d+d+d+d+d+d+d+d+d+d+d+d+d+d+d+d+eeeeeeeeeeeeeeeed+1&d+1,o3g>dg4gb4.r1<b>f+r1r4.f+r4cg>e<g>ce4<g>f4<g>e4<g>ec<g>d+c<g>d+c<g>c<g>d+c<g>d+c<g4<g>dgdgg4db4gd4db4>d+<f+b4f+b4f+r1cg>c<cg>c4<g>ec<g>c4<g>cd+<c>c<g>cd+c<g>cd+<g>c<g4>d+c4<<g>dbdgb4br1f+br1r2.cgr1r2.cr2.rgr4gr2<g2.>b16>c16<b4g4d4<g4.b2.r4.>b4f+4<b4.>c2.r4.>c4<g4c4.c1&c1<g4>d4gb4g4<g>gd2f+4b4f+b4f+r>d+<bf+b4f+b4cg>c4<g>c4<g>ec<g>c4<g>ec<cg>c<g>d+c<g>c<cg>c<g>d+c<g4<g>dgdgb4d>gd<bg4gb4f+b>d+<b>d+4<br1rcr1r2.rcr4gr1r2<g2.>b16>c16<b4g4d4<g4.b2.r4.>b4f+4<b4.>c2.r4.>c4<g4c4.c2.r1r4<ggggggggggggggggbbbbbbbbbbbbbbbb>cccccccccccccccccccccccccccccccc<ggggggggggggggggbbbbbbbbbbbbbbbb>ccccccccccccccccc1&c1<g>dgdgbgd<g>dgdgb4.<b>f+bf+b>d+4<b4<b>f+bf+>d+4<f+cg>c<g>ce4c4<g>c<g>ce4c<cg>c<g>cd+4c4<cg4c4r4d2.&d.r16d4.d4dd4f+2.&f+.r16f+4.b4a<b>f+c2&c.r16c4c4.g4.g4c2.&c16r16cc4.b4.a4";
 
-pub fn play<I: Instrument>(mml: &str) -> DynSampler {
-    let notes: HashMap<&str, f64> = [
-        ("c", C),
-        ("c+", C_SHARP_D_FLAT),
-        ("d-", C_SHARP_D_FLAT),
-        ("d", D),
-        ("d+", D_SHARP_E_FLAT),
-        ("e-", D_SHARP_E_FLAT),
-        ("e", E),
-        ("f", F),
-        ("f+", F_SHARP_G_FLAT),
-        ("g-", F_SHARP_G_FLAT),
-        ("g", G),
-        ("g+", G_SHARP_A_FLAT),
-        ("a-", G_SHARP_A_FLAT),
-        ("a", A),
-        ("a+", A_SHARP_B_FLAT),
-        ("b-", A_SHARP_B_FLAT),
-        ("b", B),
-        ("b+", C),
-        ("c-", B),
-        ("p", 0.0),
-        ("r", 0.0),
-    ]
-    .into_iter()
-    .collect();
+// Every channel (separated by `,`) becomes the `channel` of its notes.
+// `~n` slides every following note from the previous one over `n`
+// milliseconds, `~+n` at `n` semitones per second instead. `~0` turns it
+// off and rests interrupt it.
+pub fn parse(mml: &str) -> Vec<NoteEvent> {
+    parse_song(mml).0
+}
 
-    let mut subsongs: Vec<(f64, DynSampler)> = vec![];
+// The notes together with every `t` command, so tempo synced effects can
+// follow the song
+pub fn parse_song(mml: &str) -> (Vec<NoteEvent>, TempoMap) {
+    let mut events = vec![];
+    let mut tempo_map = TempoMap::default();
     let mut oct = 4;
     let mut length = 1;
     let mut tempo = 80;
     let mut volume = 120;
-    for subsong_text in mml.replace("#", "+").to_lowercase().split(",") {
+    let mut glide: Option<Glide> = None;
+    for (channel, subsong_text) in mml.replace("#", "+").to_lowercase().split(",").enumerate() {
         let re = Regex::new(r"(\D\+?\-?\#?)(\d*)(\.?)").unwrap();
-        let mut music = vec![];
         let mut time = 0f64;
+        let mut previous: Option<i32> = None;
         for cap in re.captures_iter(subsong_text) {
             match cap[1].to_string().as_str() {
                 "o" => {
@@ -57,6 +43,7 @@ pub fn play<I: Instrument>(mml: &str) -> DynSampler {
                 }
                 "t" => {
                     tempo = cap[2].parse().unwrap();
+                    tempo_map.set(time, tempo as f64);
                 }
                 "l" => {
                     length = cap[2].parse().unwrap();
@@ -64,6 +51,14 @@ pub fn play<I: Instrument>(mml: &str) -> DynSampler {
                 "v" => {
                     volume = cap[2].parse().unwrap();
                 }
+                "~" => {
+                    let time = cap[2].parse::<f64>().unwrap_or(0.0) / 1000.0;
+                    glide = Some(Glide::constant(time)).filter(|_| time > 0.0);
+                }
+                "~+" => {
+                    let rate = cap[2].parse::<f64>().unwrap_or(0.0);
+                    glide = Some(rate).filter(|&r| r > 0.0).map(Glide::rate);
+                }
                 ">" => {
                     oct += 1;
                 }
@@ -72,20 +67,60 @@ pub fn play<I: Instrument>(mml: &str) -> DynSampler {
                 }
                 "&" => {}
                 note => {
-                    if let Some(freq) = notes.get(note) {
+                    let semitones = match note {
+                        "p" | "r" => Some(None),
+                        name => semitone(name).map(Some),
+                    };
+                    if let Some(semitones) = semitones {
                         let dotted = &cap[3] == ".";
-                        let freq = on_octave(*freq, oct);
-                        let l =
-                            320.0 / (tempo as f64) / cap[2].parse::<f64>().unwrap_or(length as f64)
-                                * if dotted { 1.5 } else { 1.0 };
-                        music.push((time, I::play(freq, l, volume as f64 / 200.0)));
+                        let l = note_length(
+                            tempo as f64,
+                            cap[2].parse::<f64>().unwrap_or(length as f64),
+                            dotted,
+                        );
+                        let key = semitones.map(|semitones| 12 * (oct + 1) + semitones);
+                        if let Some(key) = key {
+                            let velocity = volume as f64 / 200.0;
+                            let mut event = NoteEvent::new(key as f64, velocity, l)
+                                .at(time)
+                                .with_channel(channel);
+                            if let (Some(from), Some(glide)) = (previous, glide) {
+                                event = glide.slide(event, (from - key) as f64);
+                            }
+                            events.push(event);
+                        }
+                        previous = key;
                         time += l;
                     }
                 }
             }
         }
-        subsongs.push((0.0, Compound::play(music)));
     }
+    (events, tempo_map)
+}
+
+pub fn render(instrument: &dyn Instrument, events: &[NoteEvent]) -> DynSampler {
+    instrument.voices().render(instrument, events)
+}
+
+pub fn play(instrument: &dyn Instrument, mml: &str) -> DynSampler {
+    render(instrument, &parse(mml))
+}
+
+// A single channel of a song, e.g. the kick channel keying a sidechain
+pub fn render_channel(
+    instrument: &dyn Instrument,
+    events: &[NoteEvent],
+    channel: usize,
+) -> DynSampler {
+    let events: Vec<NoteEvent> = events
+        .iter()
+        .filter(|e| e.channel == channel)
+        .cloned()
+        .collect();
+    render(instrument, &events)
+}
 
-    Compound::play(subsongs)
+pub fn play_channel(instrument: &dyn Instrument, mml: &str, channel: usize) -> DynSampler {
+    render_channel(instrument, &parse(mml), channel)
 }
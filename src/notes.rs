@@ -14,3 +14,31 @@ pub const B: f64 = 493.88;
 pub fn on_octave(note: f64, octave: u8) -> f64 {
     note * (2f64.powf(((octave as i8) - 4) as f64))
 }
+
+// MIDI note numbers, 69 is A4 at 440 Hz
+pub fn midi_to_frequency(note: f64) -> f64 {
+    A * 2f64.powf((note - 69.0) / 12.0)
+}
+
+pub fn frequency_to_midi(frequency: f64) -> f64 {
+    69.0 + 12.0 * (frequency / A).log2()
+}
+
+// Note names like `c`, `d+` or `e-` in semitones above C
+pub fn semitone(name: &str) -> Option<i32> {
+    Some(match name {
+        "c" | "b+" => 0,
+        "c+" | "d-" => 1,
+        "d" => 2,
+        "d+" | "e-" => 3,
+        "e" => 4,
+        "f" => 5,
+        "f+" | "g-" => 6,
+        "g" => 7,
+        "g+" | "a-" => 8,
+        "a" => 9,
+        "a+" | "b-" => 10,
+        "b" | "c-" => 11,
+        _ => return None,
+    })
+}
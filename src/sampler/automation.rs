@@ -0,0 +1,265 @@
+use super::*;
+use std::io;
+
+// How a segment gets from one breakpoint to the next. The control values
+// of `Bezier` are relative, 0 is the starting value and 1 the ending one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AutomationCurve {
+    Step,
+    Linear,
+    Exponential,
+    Bezier(f64, f64),
+    CatmullRom,
+}
+
+#[derive(Clone)]
+enum Shape {
+    Step,
+    Line(DynSampler, DynSampler),
+    Exponential(f64),
+    // Polynomial in the normalized position within the segment
+    Cubic([f64; 4]),
+}
+
+#[derive(Clone)]
+struct Segment {
+    start: f64,
+    end: f64,
+    from: f64,
+    shape: Shape,
+}
+
+impl Segment {
+    fn position(&self, t: f64) -> f64 {
+        ((t - self.start) / (self.end - self.start)).clamp(0.0, 1.0)
+    }
+    fn value(&self, t: f64) -> f64 {
+        let x = self.position(t);
+        match &self.shape {
+            Shape::Step => self.from,
+            Shape::Line(line, _) => line.sample(t.clamp(self.start, self.end)),
+            Shape::Exponential(ratio) => self.from * ratio.powf(x),
+            Shape::Cubic(c) => ((c[3] * x + c[2]) * x + c[1]) * x + c[0],
+        }
+    }
+    // Area from the start of the segment up to `t`
+    fn area(&self, t: f64) -> f64 {
+        let x = self.position(t);
+        let length = self.end - self.start;
+        match &self.shape {
+            Shape::Step => self.from * length * x,
+            Shape::Line(_, integral) => {
+                integral.sample(t.clamp(self.start, self.end)) - integral.sample(self.start)
+            }
+            Shape::Exponential(ratio) => length * self.from * (ratio.powf(x) - 1.0) / ratio.ln(),
+            Shape::Cubic(c) => {
+                length * x * (c[0] + x * (c[1] / 2.0 + x * (c[2] / 3.0 + x * c[3] / 4.0)))
+            }
+        }
+    }
+}
+
+// Breakpoint automation, holds the first and last values outside of the
+// breakpoints
+#[derive(Clone)]
+pub struct Automation {
+    points: Vec<(f64, f64)>,
+    curves: Vec<AutomationCurve>,
+    segments: Vec<Segment>,
+    // Area from the first breakpoint to the start of every segment
+    areas: Vec<f64>,
+}
+
+impl Automation {
+    pub fn new(mut points: Vec<(f64, f64)>, mut curves: Vec<AutomationCurve>) -> Self {
+        assert!(!points.is_empty(), "No breakpoints!");
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let count = points.len() - 1;
+        let last = curves.last().cloned().unwrap_or(AutomationCurve::Linear);
+        curves.resize(count, last);
+
+        let segments: Vec<Segment> = (0..count)
+            .filter(|&i| points[i + 1].0 > points[i].0)
+            .map(|i| {
+                let (t0, v0) = points[i];
+                let (t1, v1) = points[i + 1];
+                let shape = match curves[i] {
+                    AutomationCurve::Step => Shape::Step,
+                    AutomationCurve::Exponential if v0 * v1 > 0.0 && v0 != v1 => {
+                        Shape::Exponential(v1 / v0)
+                    }
+                    AutomationCurve::Linear | AutomationCurve::Exponential => {
+                        let line = Line::interpolate((t0, v0), (t1, v1));
+                        let integral = line.integral();
+                        Shape::Line(line, integral)
+                    }
+                    AutomationCurve::Bezier(c1, c2) => {
+                        let p1 = v0 + (v1 - v0) * c1;
+                        let p2 = v0 + (v1 - v0) * c2;
+                        Shape::Cubic([
+                            v0,
+                            3.0 * (p1 - v0),
+                            3.0 * (v0 - 2.0 * p1 + p2),
+                            v1 - v0 + 3.0 * (p1 - p2),
+                        ])
+                    }
+                    AutomationCurve::CatmullRom => {
+                        // Tangents from the neighbouring breakpoints, scaled
+                        // to the length of this segment
+                        let length = t1 - t0;
+                        let (ta, va) = if i > 0 { points[i - 1] } else { (t0, v0) };
+                        let (tb, vb) = points.get(i + 2).cloned().unwrap_or((t1, v1));
+                        let m0 = if t1 > ta {
+                            (v1 - va) / (t1 - ta) * length
+                        } else {
+                            0.0
+                        };
+                        let m1 = if tb > t0 {
+                            (vb - v0) / (tb - t0) * length
+                        } else {
+                            0.0
+                        };
+                        Shape::Cubic([
+                            v0,
+                            m0,
+                            3.0 * (v1 - v0) - 2.0 * m0 - m1,
+                            2.0 * (v0 - v1) + m0 + m1,
+                        ])
+                    }
+                };
+                Segment {
+                    start: t0,
+                    end: t1,
+                    from: v0,
+                    shape,
+                }
+            })
+            .collect();
+
+        let areas = segments
+            .iter()
+            .scan(0.0, |total, s| {
+                let before = *total;
+                *total += s.area(s.end);
+                Some(before)
+            })
+            .collect();
+        Self {
+            points,
+            curves,
+            segments,
+            areas,
+        }
+    }
+    pub fn uniform(points: Vec<(f64, f64)>, curve: AutomationCurve) -> Self {
+        Self::new(points, vec![curve])
+    }
+    pub fn points(&self) -> &[(f64, f64)] {
+        &self.points
+    }
+    pub fn curves(&self) -> &[AutomationCurve] {
+        &self.curves
+    }
+
+    // One breakpoint per line, `time value [curve]` where the curve is one
+    // of `step`, `linear`, `exp`, `bezier c1 c2` or `catmull` and shapes the
+    // segment that starts at the breakpoint. `#` starts a comment.
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |line: usize, msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", line + 1, msg),
+            )
+        };
+        let mut points = Vec::new();
+        let mut curves = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let number = |index: usize| -> io::Result<f64> {
+                fields
+                    .get(index)
+                    .ok_or_else(|| invalid(i, "missing value"))?
+                    .parse()
+                    .map_err(|_| invalid(i, "invalid number"))
+            };
+            points.push((number(0)?, number(1)?));
+            curves.push(match fields.get(2).cloned().unwrap_or("linear") {
+                "step" => AutomationCurve::Step,
+                "linear" => AutomationCurve::Linear,
+                "exp" => AutomationCurve::Exponential,
+                "bezier" => AutomationCurve::Bezier(number(3)?, number(4)?),
+                "catmull" => AutomationCurve::CatmullRom,
+                _ => return Err(invalid(i, "unknown curve")),
+            });
+        }
+        if points.is_empty() {
+            return Err(invalid(0, "no breakpoints"));
+        }
+        curves.pop();
+        Ok(Self::new(points, curves))
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    fn segment(&self, t: f64) -> Option<usize> {
+        let index = self.segments.partition_point(|s| s.start <= t);
+        index.checked_sub(1)
+    }
+    // Area from the first breakpoint to `t`, negative before it
+    fn area(&self, t: f64) -> f64 {
+        let (first_time, first_value) = self.points[0];
+        let (last_time, last_value) = self.points[self.points.len() - 1];
+        if t <= first_time || self.segments.is_empty() {
+            return (t - first_time) * first_value;
+        }
+        if t >= last_time {
+            let total = self.areas.last().unwrap() + {
+                let s = self.segments.last().unwrap();
+                s.area(s.end)
+            };
+            return total + (t - last_time) * last_value;
+        }
+        let i = self.segment(t).unwrap();
+        self.areas[i] + self.segments[i].area(t)
+    }
+}
+
+impl Sampler for Automation {
+    fn sample(&self, t: f64) -> f64 {
+        let (first_time, first_value) = self.points[0];
+        let (last_time, last_value) = self.points[self.points.len() - 1];
+        if t < first_time {
+            first_value
+        } else if t >= last_time {
+            last_value
+        } else {
+            match self.segment(t) {
+                Some(i) => self.segments[i].value(t),
+                None => first_value,
+            }
+        }
+    }
+    fn integral(&self) -> DynSampler {
+        Box::new(AutomationIntegral {
+            offset: self.area(0.0),
+            automation: self.clone(),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct AutomationIntegral {
+    automation: Automation,
+    offset: f64,
+}
+
+impl Sampler for AutomationIntegral {
+    fn sample(&self, t: f64) -> f64 {
+        self.automation.area(t) - self.offset
+    }
+}
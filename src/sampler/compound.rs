@@ -16,65 +16,15 @@ impl Compound {
         release_length: f64,
         sustain_level: f64,
     ) -> DynSampler {
-        Compound::new(vec![
-            (
-                1.0,
-                Limit::new(
-                    Line::interpolate((0.0, 0.0), (attack_length, 1.0)),
-                    0.0,
-                    attack_length,
-                ),
-            ),
-            (
-                1.0,
-                Limit::new(
-                    Line::interpolate(
-                        (attack_length, 1.0),
-                        (attack_length + decay_length, sustain_level),
-                    ),
-                    attack_length,
-                    attack_length + decay_length,
-                ),
-            ),
-            (
-                1.0,
-                Limit::new(
-                    Const::new(sustain_level),
-                    attack_length + decay_length,
-                    attack_length + decay_length + sustain_length,
-                ),
-            ),
-            (
-                1.0,
-                Limit::new(
-                    Line::interpolate(
-                        (attack_length + decay_length + sustain_length, sustain_level),
-                        (
-                            attack_length + decay_length + sustain_length + release_length,
-                            0.0,
-                        ),
-                    ),
-                    attack_length + decay_length + sustain_length,
-                    attack_length + decay_length + sustain_length + release_length,
-                ),
-            ),
-        ])
+        Envelope::adsr(attack_length, decay_length, sustain_level, release_length)
+            .gated(attack_length + decay_length + sustain_length)
     }
 
     pub fn unison<F>(pitch: f64, count: usize, creator: F) -> DynSampler
     where
         F: Fn(f64) -> DynSampler,
     {
-        if count % 2 == 0 {
-            panic!("Not supported!");
-        }
-        let pows = -(count as isize / 2)..(count as isize / 2 + 1);
-        Compound::new(
-            pows.into_iter()
-                .map(|p| pitch * 2f64.powf(p as f64))
-                .map(|f| (1.0, creator(f)))
-                .collect(),
-        )
+        Unison::new(count, 20.0).build(pitch, creator)
     }
     pub fn play(events: Vec<(f64, DynSampler)>) -> DynSampler {
         Compound::new(
@@ -0,0 +1,202 @@
+use super::*;
+
+// A segment that moves from the previous level to `level` in `length`
+// seconds. A `curve` of zero is a straight line, negative values start fast
+// and settle slowly (like an analog RC envelope), positive values start
+// slowly.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Stage {
+    pub length: f64,
+    pub level: f64,
+    pub curve: f64,
+}
+
+impl Stage {
+    pub fn new(length: f64, level: f64, curve: f64) -> Self {
+        Self {
+            length,
+            level,
+            curve,
+        }
+    }
+    pub fn linear(length: f64, level: f64) -> Self {
+        Self::new(length, level, 0.0)
+    }
+
+    fn shape(&self, x: f64) -> f64 {
+        if self.curve.abs() < 1e-6 {
+            x
+        } else {
+            (1.0 - (self.curve * x).exp()) / (1.0 - self.curve.exp())
+        }
+    }
+    // Integral of `shape` from 0 to `x`
+    fn shape_integral(&self, x: f64) -> f64 {
+        if self.curve.abs() < 1e-6 {
+            x * x / 2.0
+        } else {
+            (x - ((self.curve * x).exp() - 1.0) / self.curve) / (1.0 - self.curve.exp())
+        }
+    }
+    // Level and area after `time` seconds into the stage, starting at `from`
+    fn at(&self, from: f64, time: f64) -> (f64, f64) {
+        if self.length <= 0.0 {
+            return (self.level, 0.0);
+        }
+        let x = (time / self.length).clamp(0.0, 1.0);
+        let delta = self.level - from;
+        (
+            from + delta * self.shape(x),
+            self.length * (from * x + delta * self.shape_integral(x)),
+        )
+    }
+}
+
+// Gate driven multi-stage envelope. While the gate is open `stages` run in
+// order and the last level is held, or `stages[loop_start..]` repeat if a
+// loop is set. Once the gate closes the `release` stages run starting from
+// whatever level the envelope had reached.
+#[derive(Clone)]
+pub struct Envelope {
+    pub stages: Vec<Stage>,
+    pub release: Vec<Stage>,
+    pub loop_start: Option<usize>,
+    // Level the first stage starts from, non-zero when retriggering a
+    // sounding note
+    pub start: f64,
+    // Time the gate closes, the envelope sustains forever if `None`
+    pub gate: Option<f64>,
+}
+
+// Walks through `stages` for `time` seconds, returning the level, the area
+// and the time left once all of them are over
+fn walk(stages: &[Stage], mut level: f64, mut time: f64) -> (f64, f64, f64) {
+    let mut area = 0.0;
+    for stage in stages {
+        if time <= 0.0 {
+            break;
+        }
+        let (l, a) = stage.at(level, time);
+        level = l;
+        area += a;
+        time -= stage.length.max(0.0);
+    }
+    (level, area, time.max(0.0))
+}
+
+impl Envelope {
+    pub fn new(stages: Vec<Stage>, release: Vec<Stage>) -> Self {
+        Self {
+            stages,
+            release,
+            loop_start: None,
+            start: 0.0,
+            gate: None,
+        }
+    }
+    pub fn adsr(attack: f64, decay: f64, sustain: f64, release: f64) -> Self {
+        Self::new(
+            vec![Stage::linear(attack, 1.0), Stage::linear(decay, sustain)],
+            vec![Stage::linear(release, 0.0)],
+        )
+    }
+    pub fn dahdsr(
+        delay: f64,
+        attack: f64,
+        hold: f64,
+        decay: f64,
+        sustain: f64,
+        release: f64,
+    ) -> Self {
+        Self::new(
+            vec![
+                Stage::linear(delay, 0.0),
+                Stage::linear(attack, 1.0),
+                Stage::linear(hold, 1.0),
+                Stage::linear(decay, sustain),
+            ],
+            vec![Stage::linear(release, 0.0)],
+        )
+    }
+    pub fn with_curve(mut self, curve: f64) -> Self {
+        self.stages
+            .iter_mut()
+            .chain(self.release.iter_mut())
+            .for_each(|s| s.curve = curve);
+        self
+    }
+    pub fn with_loop(mut self, loop_start: usize) -> Self {
+        self.loop_start = Some(loop_start);
+        self
+    }
+    pub fn with_start(mut self, start: f64) -> Self {
+        self.start = start;
+        self
+    }
+    pub fn with_gate(mut self, gate: f64) -> Self {
+        self.gate = Some(gate);
+        self
+    }
+    pub fn gated(&self, gate: f64) -> DynSampler {
+        Box::new(self.clone().with_gate(gate))
+    }
+    pub fn length(&self) -> Option<f64> {
+        self.gate
+            .map(|gate| gate + self.release.iter().map(|s| s.length.max(0.0)).sum::<f64>())
+    }
+
+    fn held(&self, time: f64) -> (f64, f64) {
+        let (mut level, mut area, mut time) = walk(&self.stages, self.start, time);
+        if let Some(loop_start) = self.loop_start.filter(|l| *l < self.stages.len()) {
+            let cycle = &self.stages[loop_start..];
+            let period: f64 = cycle.iter().map(|s| s.length.max(0.0)).sum();
+            if time > 0.0 && period > 0.0 {
+                // Every complete cycle starts and ends on the last level
+                let (_, cycle_area, _) = walk(cycle, level, period);
+                let cycles = (time / period).floor();
+                area += cycles * cycle_area;
+                let (l, a, _) = walk(cycle, level, time - cycles * period);
+                level = l;
+                area += a;
+                time = 0.0;
+            }
+        }
+        (level, area + level * time)
+    }
+
+    // Level and integral at `t`
+    fn evaluate(&self, t: f64) -> (f64, f64) {
+        if t <= 0.0 {
+            return (0.0, 0.0);
+        }
+        let gate = self.gate.unwrap_or(f64::INFINITY);
+        let (level, area) = self.held(t.min(gate));
+        if t <= gate {
+            return (level, area);
+        }
+        let (level, released, time) = walk(&self.release, level, t - gate);
+        (level, area + released + level * time)
+    }
+}
+
+impl Sampler for Envelope {
+    fn sample(&self, t: f64) -> f64 {
+        self.evaluate(t).0
+    }
+    fn integral(&self) -> DynSampler {
+        Box::new(EnvelopeIntegral {
+            envelope: self.clone(),
+        })
+    }
+}
+
+#[derive(Clone)]
+struct EnvelopeIntegral {
+    envelope: Envelope,
+}
+
+impl Sampler for EnvelopeIntegral {
+    fn sample(&self, t: f64) -> f64 {
+        self.envelope.evaluate(t).1
+    }
+}
@@ -0,0 +1,168 @@
+use super::*;
+use std::f64::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FadeCurve {
+    Linear,
+    EqualPower,
+    RaisedCosine,
+}
+
+impl FadeCurve {
+    // Gain of a fade in at `x` between 0 and 1. Linear and raised cosine
+    // fades sum to one with their mirrored fade out, equal power fades
+    // keep the power of uncorrelated signals constant instead.
+    pub fn gain(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => x,
+            FadeCurve::EqualPower => (x * PI / 2.0).sin(),
+            FadeCurve::RaisedCosine => 0.5 - 0.5 * (x * PI).cos(),
+        }
+    }
+    // Area under the gain from 0 to `x`
+    pub fn area(&self, x: f64) -> f64 {
+        let x = x.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => x * x / 2.0,
+            FadeCurve::EqualPower => (1.0 - (x * PI / 2.0).cos()) * 2.0 / PI,
+            FadeCurve::RaisedCosine => x / 2.0 - (x * PI).sin() / (2.0 * PI),
+        }
+    }
+}
+
+// Like `Limit`, but fades in and out instead of switching. The fades are
+// centered on `start` and `end`, so two fades sharing a boundary crossfade.
+#[derive(Clone)]
+pub struct Fade {
+    pub sampler: DynSampler,
+    pub start: f64,
+    pub end: f64,
+    pub length: f64,
+    pub curve: FadeCurve,
+}
+
+impl Fade {
+    pub fn new(
+        sampler: DynSampler,
+        start: f64,
+        end: f64,
+        length: f64,
+        curve: FadeCurve,
+    ) -> DynSampler {
+        Box::new(Fade {
+            sampler,
+            start,
+            end,
+            length: length.min(end - start).max(0.0),
+            curve,
+        })
+    }
+    fn gain(&self, t: f64) -> f64 {
+        let half = self.length / 2.0;
+        if t < self.start - half || t > self.end + half {
+            0.0
+        } else if self.length <= 0.0 {
+            1.0
+        } else if t < self.start + half {
+            self.curve.gain((t - self.start + half) / self.length)
+        } else if t > self.end - half {
+            self.curve.gain((self.end + half - t) / self.length)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Sampler for Fade {
+    fn sample(&self, t: f64) -> f64 {
+        let gain = self.gain(t);
+        if gain == 0.0 {
+            0.0
+        } else {
+            self.sampler.sample(t) * gain
+        }
+    }
+    fn integral(&self) -> DynSampler {
+        Box::new(FadeIntegral {
+            integral: self.sampler.integral(),
+            fade: self.clone(),
+        })
+    }
+}
+
+// The steady part is integrated exactly through the integral of the faded
+// sampler. Within a fade the sampler is approximated by its mean up to `t`,
+// which scales the exact area under the curve. This is exact for samplers
+// that are constant during the fades and otherwise off by at most half the
+// fade length times how far the sampler swings (max - min) within the fade,
+// for each fade.
+#[derive(Clone)]
+struct FadeIntegral {
+    fade: Fade,
+    integral: DynSampler,
+}
+
+impl FadeIntegral {
+    fn mean(&self, a: f64, b: f64) -> f64 {
+        if b <= a {
+            0.0
+        } else {
+            (self.integral.sample(b) - self.integral.sample(a)) / (b - a)
+        }
+    }
+}
+
+impl Sampler for FadeIntegral {
+    fn sample(&self, t: f64) -> f64 {
+        let length = self.fade.length;
+        let curve = self.fade.curve;
+        let half = length / 2.0;
+        let (a, b) = (self.fade.start - half, self.fade.start + half);
+        let (c, d) = (self.fade.end - half, self.fade.end + half);
+        if t <= a {
+            return 0.0;
+        }
+        let x = t.min(b);
+        let fade_in = self.mean(a, x) * length * curve.area((x - a) / length.max(1e-12));
+        if t <= b {
+            return fade_in;
+        }
+        let steady = self.integral.sample(t.min(c)) - self.integral.sample(b);
+        let x = t.min(d);
+        let fade_out =
+            self.mean(c, x) * length * (curve.area(1.0) - curve.area((d - x) / length.max(1e-12)));
+        fade_in + steady + fade_out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fade_integrals_stay_within_their_error_bound() {
+        let (length, steps) = (0.01, 100000);
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::EqualPower,
+            FadeCurve::RaisedCosine,
+        ] {
+            let constant = Fade::new(Const::new(2.0), 1.0, 2.0, length, curve).integral();
+            let area = 2.0 * (1.0 - length + 2.0 * length * curve.area(1.0));
+            assert!((constant.sample(3.0) - area).abs() < 1e-9);
+
+            let fade = Fade::new(Sine::sin(300.0), 1.0, 2.0, length, curve);
+            let integral = fade.integral();
+            // Two fades of a sine swinging by 2
+            let bound = 2.0 * length / 2.0 * 2.0;
+            let h = 1.5 / steps as f64;
+            let mut sum = 0.0;
+            for i in 0..steps {
+                let t = 0.75 + h * i as f64;
+                sum += fade.sample(t + h / 2.0) * h;
+                assert!((integral.sample(t + h) - sum).abs() <= bound + 1e-6);
+            }
+        }
+    }
+}
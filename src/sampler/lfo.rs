@@ -0,0 +1,112 @@
+use super::*;
+use crate::tempo::Length;
+
+// Low frequency oscillator, silent for `delay` seconds and then fading in
+// over `fade_in` seconds
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lfo {
+    pub waveform: Waveform,
+    pub rate: f64,
+    pub depth: f64,
+    pub delay: f64,
+    pub fade_in: f64,
+    // Start phase in cycles
+    pub phase: f64,
+}
+
+impl Lfo {
+    pub fn new(waveform: Waveform, rate: f64, depth: f64) -> Self {
+        Self {
+            waveform,
+            rate,
+            depth,
+            delay: 0.0,
+            fade_in: 0.0,
+            phase: 0.0,
+        }
+    }
+    // One cycle per `period`, e.g. a quarter note at the tempo of the song
+    pub fn synced(waveform: Waveform, period: Length, depth: f64) -> Self {
+        Self::new(waveform, 1.0 / period.seconds(), depth)
+    }
+    pub fn with_delay(mut self, delay: f64, fade_in: f64) -> Self {
+        self.delay = delay;
+        self.fade_in = fade_in;
+        self
+    }
+    pub fn with_phase(mut self, phase: f64) -> Self {
+        self.phase = phase;
+        self
+    }
+    fn fade(&self, t: f64) -> f64 {
+        if t < self.delay {
+            0.0
+        } else if self.fade_in > 0.0 {
+            ((t - self.delay) / self.fade_in).min(1.0)
+        } else {
+            1.0
+        }
+    }
+}
+
+impl Sampler for Lfo {
+    fn sample(&self, t: f64) -> f64 {
+        let fade = self.fade(t);
+        if fade == 0.0 {
+            0.0
+        } else {
+            self.depth * fade * self.waveform.at(self.rate * t + self.phase)
+        }
+    }
+    fn integral(&self) -> DynSampler {
+        Box::new(LfoIntegral { lfo: *self })
+    }
+}
+
+// Exact once the LFO is fully faded in, the fade itself is integrated
+// numerically
+#[derive(Clone)]
+struct LfoIntegral {
+    lfo: Lfo,
+}
+
+impl LfoIntegral {
+    fn steady(&self, from: f64, to: f64) -> f64 {
+        let w = |t: f64| {
+            self.lfo
+                .waveform
+                .integral(self.lfo.rate * t + self.lfo.phase)
+        };
+        if self.lfo.rate == 0.0 {
+            self.lfo.depth * self.lfo.waveform.at(self.lfo.phase) * (to - from)
+        } else {
+            self.lfo.depth * (w(to) - w(from)) / self.lfo.rate
+        }
+    }
+}
+
+impl Sampler for LfoIntegral {
+    fn sample(&self, t: f64) -> f64 {
+        const STEPS: usize = 256;
+        let start = self.lfo.delay;
+        if t <= start {
+            return 0.0;
+        }
+        let faded = start + self.lfo.fade_in;
+        let end = t.min(faded);
+        let mut area = 0.0;
+        if end > start {
+            let h = (end - start) / STEPS as f64;
+            let mut sum = self.lfo.sample(start) + self.lfo.sample(end);
+            for i in 1..STEPS {
+                let weight = if i % 2 == 1 { 4.0 } else { 2.0 };
+                sum += weight * self.lfo.sample(start + h * i as f64);
+            }
+            area += sum * h / 3.0;
+        }
+        if t > faded {
+            area += self.steady(faded, t);
+        }
+        area
+    }
+}
@@ -1,21 +1,42 @@
+mod automation;
 mod compound;
+mod envelope;
+mod fade;
+mod lfo;
 mod limit;
 mod linear;
 mod modulator;
 mod oscillator;
+mod playback;
+mod random;
 mod record;
+mod shaper;
 mod signal;
+mod unison;
+mod wav;
 mod window;
 
+pub use automation::*;
 pub use compound::*;
+pub use envelope::*;
+pub use fade::*;
+pub use lfo::*;
 pub use limit::*;
 pub use linear::*;
 pub use modulator::*;
 pub use oscillator::*;
+pub use playback::*;
 pub use record::*;
+pub use shaper::*;
 pub use signal::*;
+pub use unison::*;
+pub use wav::*;
 pub use window::*;
 
+pub(crate) use random::*;
+#[cfg(test)]
+pub(crate) use wav::tests as wav_tests;
+
 use dyn_clone::DynClone;
 
 pub trait Sampler: DynClone + Send + Sync {
@@ -0,0 +1,123 @@
+use super::*;
+use std::sync::Arc;
+
+// Offsets the phase of the carrier by the modulator, `index` is in cycles.
+// Unlike `FrequencyModulator` the modulator needs no integral.
+#[derive(Clone)]
+pub struct PhaseModulator {
+    waveform: Waveform,
+    frequency: f64,
+    modulator: DynSampler,
+    index: f64,
+}
+
+impl PhaseModulator {
+    pub fn new(
+        waveform: Waveform,
+        frequency: f64,
+        modulator: DynSampler,
+        index: f64,
+    ) -> DynSampler {
+        Box::new(Self {
+            waveform,
+            frequency,
+            modulator,
+            index,
+        })
+    }
+}
+
+impl Sampler for PhaseModulator {
+    fn sample(&self, t: f64) -> f64 {
+        self.waveform
+            .at(self.frequency * t + self.index * self.modulator.sample(t))
+    }
+}
+
+// Through-zero linear frequency modulation between oscillators. The phase
+// is accumulated sample by sample over `duration`, so the modulator needs
+// no integral and two oscillators may modulate each other.
+#[derive(Clone)]
+pub struct CrossModulator {
+    waveform: Waveform,
+    frequency: f64,
+    sample_rate: f64,
+    phases: Arc<Vec<f64>>,
+}
+
+impl CrossModulator {
+    pub fn new(
+        waveform: Waveform,
+        frequency: f64,
+        modulator: DynSampler,
+        depth: f64,
+        sample_rate: f64,
+        duration: f64,
+    ) -> DynSampler {
+        let step = 1.0 / sample_rate;
+        let mut phase = 0.0;
+        let phases = (0..(duration * sample_rate) as usize)
+            .map(|i| {
+                let current = phase;
+                phase += (frequency + depth * modulator.sample(i as f64 * step)) * step;
+                current
+            })
+            .collect();
+        Box::new(Self {
+            waveform,
+            frequency,
+            sample_rate,
+            phases: Arc::new(phases),
+        })
+    }
+
+    // Two oscillators, each modulating the frequency of the other by
+    // `depth` Hz at full swing
+    pub fn mutual(
+        a: (Waveform, f64, f64),
+        b: (Waveform, f64, f64),
+        sample_rate: f64,
+        duration: f64,
+    ) -> (DynSampler, DynSampler) {
+        let step = 1.0 / sample_rate;
+        let (mut pa, mut pb) = (0.0, 0.0);
+        let (phases_a, phases_b) = (0..(duration * sample_rate) as usize)
+            .map(|_| {
+                let current = (pa, pb);
+                let (va, vb) = (a.0.at(pa), b.0.at(pb));
+                pa += (a.1 + a.2 * vb) * step;
+                pb += (b.1 + b.2 * va) * step;
+                current
+            })
+            .unzip();
+        let oscillator = |(waveform, frequency, _): (Waveform, f64, f64), phases| -> DynSampler {
+            Box::new(Self {
+                waveform,
+                frequency,
+                sample_rate,
+                phases: Arc::new(phases),
+            })
+        };
+        (oscillator(a, phases_a), oscillator(b, phases_b))
+    }
+
+    fn phase(&self, t: f64) -> f64 {
+        let position = (t * self.sample_rate).max(0.0);
+        let index = position as usize;
+        match (self.phases.get(index), self.phases.get(index + 1)) {
+            (Some(a), Some(b)) => a + (b - a) * (position - index as f64),
+            _ => {
+                // Free running at the carrier frequency after the end
+                let last = self.phases.len().saturating_sub(1);
+                let end = self.phases.last().cloned().unwrap_or(0.0);
+                end + (t - last as f64 / self.sample_rate) * self.frequency
+            }
+        }
+    }
+}
+
+impl Sampler for CrossModulator {
+    fn sample(&self, t: f64) -> f64 {
+        self.waveform.at(self.phase(t))
+    }
+}
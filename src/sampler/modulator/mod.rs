@@ -1,7 +1,11 @@
 use super::*;
 
 mod ampmod;
+mod crossmod;
 mod freqmod;
+mod ringmod;
 
 pub use ampmod::*;
+pub use crossmod::*;
 pub use freqmod::*;
+pub use ringmod::*;
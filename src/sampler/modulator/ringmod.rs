@@ -0,0 +1,26 @@
+use super::*;
+
+// Product of two signals, `mix` blends in the unmodulated carrier
+#[derive(Clone)]
+pub struct RingModulator {
+    carrier: DynSampler,
+    modulator: DynSampler,
+    mix: f64,
+}
+
+impl RingModulator {
+    pub fn new(carrier: DynSampler, modulator: DynSampler, mix: f64) -> DynSampler {
+        Box::new(RingModulator {
+            carrier,
+            modulator,
+            mix,
+        })
+    }
+}
+
+impl Sampler for RingModulator {
+    fn sample(&self, t: f64) -> f64 {
+        let carrier = self.carrier.sample(t);
+        carrier * self.modulator.sample(t) * self.mix + carrier * (1.0 - self.mix)
+    }
+}
@@ -0,0 +1,188 @@
+use super::*;
+use std::f64::consts::PI;
+
+#[derive(Clone)]
+pub struct Partial {
+    // Frequency relative to the fundamental, needn't be whole
+    pub ratio: f64,
+    // In Hz, added to the frequency given by the ratio
+    pub offset: f64,
+    pub amplitude: f64,
+    // In cycles
+    pub phase: f64,
+    pub envelope: Option<DynSampler>,
+}
+
+impl Partial {
+    pub fn new(ratio: f64, amplitude: f64) -> Self {
+        Self {
+            ratio,
+            offset: 0.0,
+            amplitude,
+            phase: 0.0,
+            envelope: None,
+        }
+    }
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+    pub fn with_phase(mut self, phase: f64) -> Self {
+        self.phase = phase;
+        self
+    }
+    pub fn with_envelope(mut self, envelope: DynSampler) -> Self {
+        self.envelope = Some(envelope);
+        self
+    }
+    fn frequency(&self, fundamental: f64) -> f64 {
+        fundamental * self.ratio + self.offset
+    }
+}
+
+// Sum of sine partials. Partials at or above Nyquist are dropped when the
+// oscillator is created. Partials with an envelope can't be integrated, so
+// an oscillator with one can't modulate frequencies.
+#[derive(Clone)]
+pub struct Additive {
+    freq: f64,
+    partials: Vec<Partial>,
+}
+
+impl Additive {
+    pub fn new(freq: f64, partials: Vec<Partial>, sample_rate: f64) -> DynSampler {
+        Box::new(Self {
+            freq,
+            partials: partials
+                .into_iter()
+                .filter(|p| p.frequency(freq) < sample_rate / 2.0 && p.amplitude != 0.0)
+                .collect(),
+        })
+    }
+    pub fn harmonic(freq: f64, amplitudes: &[f64], sample_rate: f64) -> DynSampler {
+        Self::new(
+            freq,
+            amplitudes
+                .iter()
+                .enumerate()
+                .map(|(i, a)| Partial::new((i + 1) as f64, *a))
+                .collect(),
+            sample_rate,
+        )
+    }
+    pub fn drawbars(freq: f64, drawbars: [u8; 9], sample_rate: f64) -> DynSampler {
+        Self::new(freq, Self::drawbar_partials(drawbars), sample_rate)
+    }
+    pub fn bell(freq: f64, duration: f64, sample_rate: f64) -> DynSampler {
+        Self::new(freq, Self::bell_partials(duration), sample_rate)
+    }
+
+    // Hammond style drawbars from 16' to 1', each pulled out from 0 to 8
+    // with roughly 3 dB per step
+    pub fn drawbar_partials(drawbars: [u8; 9]) -> Vec<Partial> {
+        const FOOTAGES: [f64; 9] = [0.5, 1.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 8.0];
+        FOOTAGES
+            .iter()
+            .zip(drawbars.iter())
+            .filter(|(_, d)| **d > 0)
+            .map(|(ratio, d)| {
+                let level = 10f64.powf(-3.0 * (8 - (*d).min(8)) as f64 / 20.0);
+                Partial::new(*ratio, level / 9.0)
+            })
+            .collect()
+    }
+    // Risset's bell, inharmonic partials where the higher ones die out
+    // first. The doubled lowest partials are detuned by a few Hz to beat.
+    pub fn bell_partials(duration: f64) -> Vec<Partial> {
+        const PARTIALS: [(f64, f64, f64, f64); 11] = [
+            (0.56, 0.0, 1.0, 1.0),
+            (0.56, 1.0, 0.67, 0.9),
+            (0.92, 0.0, 1.0, 0.65),
+            (0.92, 1.7, 1.8, 0.55),
+            (1.19, 0.0, 2.67, 0.325),
+            (1.7, 0.0, 1.67, 0.35),
+            (2.0, 0.0, 1.46, 0.25),
+            (2.74, 0.0, 1.33, 0.2),
+            (3.0, 0.0, 1.33, 0.15),
+            (3.76, 0.0, 1.0, 0.1),
+            (4.07, 0.0, 1.33, 0.075),
+        ];
+        PARTIALS
+            .iter()
+            .map(|(ratio, offset, amplitude, length)| {
+                let decay = Envelope::new(vec![Stage::new(duration * length, 0.0, -4.0)], vec![])
+                    .with_start(1.0);
+                Partial::new(*ratio, amplitude / 16.0)
+                    .with_offset(*offset)
+                    .with_envelope(Box::new(decay))
+            })
+            .collect()
+    }
+
+    // Renders `out.len()` samples of `partials` over `freq` starting at
+    // `start`. Every partial is a rotating phasor, so a sample costs a
+    // complex multiplication per partial instead of a `sin`.
+    pub fn render(freq: f64, partials: &[Partial], start: f64, sample_rate: f64, out: &mut [f64]) {
+        out.iter_mut().for_each(|s| *s = 0.0);
+        for partial in partials.iter() {
+            let freq = partial.frequency(freq);
+            if freq >= sample_rate / 2.0 || partial.amplitude == 0.0 {
+                continue;
+            }
+            let angle = 2.0 * PI * (freq * start + partial.phase);
+            let (mut im, mut re) = angle.sin_cos();
+            let (step_im, step_re) = (2.0 * PI * freq / sample_rate).sin_cos();
+            for (i, s) in out.iter_mut().enumerate() {
+                let gain = match &partial.envelope {
+                    Some(envelope) => envelope.sample(start + i as f64 / sample_rate),
+                    None => 1.0,
+                };
+                *s += partial.amplitude * gain * im;
+                let next = re * step_re - im * step_im;
+                im = re * step_im + im * step_re;
+                re = next;
+                // Keep rounding errors from growing the phasor
+                if i % 1024 == 1023 {
+                    let norm = (re * re + im * im).sqrt();
+                    re /= norm;
+                    im /= norm;
+                }
+            }
+        }
+    }
+}
+
+impl Sampler for Additive {
+    fn sample(&self, t: f64) -> f64 {
+        self.partials
+            .iter()
+            .map(|p| {
+                let gain = match &p.envelope {
+                    Some(envelope) => envelope.sample(t),
+                    None => 1.0,
+                };
+                p.amplitude * gain * (2.0 * PI * (p.frequency(self.freq) * t + p.phase)).sin()
+            })
+            .sum()
+    }
+    fn integral(&self) -> DynSampler {
+        if self.partials.iter().any(|p| p.envelope.is_some()) {
+            panic!("Not supported!");
+        }
+        Compound::new(
+            self.partials
+                .iter()
+                .map(|p| {
+                    let omega = 2.0 * PI * p.frequency(self.freq);
+                    let cosine: DynSampler = Box::new(Self {
+                        freq: self.freq,
+                        partials: vec![Partial::new(p.ratio, 1.0)
+                            .with_offset(p.offset)
+                            .with_phase(p.phase + 0.25)],
+                    });
+                    (-p.amplitude / omega, cosine)
+                })
+                .collect(),
+        )
+    }
+}
@@ -1,11 +1,19 @@
 use super::*;
 
+mod additive;
+mod noise;
 mod sawtooth;
 mod sine;
 mod square;
+mod sync;
 mod triangle;
+mod waveform;
 
+pub use additive::*;
+pub use noise::*;
 pub use sawtooth::*;
 pub use sine::*;
 pub use square::*;
+pub use sync::*;
 pub use triangle::*;
+pub use waveform::*;
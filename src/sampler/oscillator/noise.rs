@@ -0,0 +1,21 @@
+use super::*;
+
+// White noise, a new value every sample
+#[derive(Clone)]
+pub struct Noise {
+    sample_rate: f64,
+    seed: u64,
+}
+
+impl Noise {
+    pub fn new(sample_rate: f64, seed: u64) -> DynSampler {
+        Box::new(Noise { sample_rate, seed })
+    }
+}
+
+impl Sampler for Noise {
+    fn sample(&self, t: f64) -> f64 {
+        let index = (t * self.sample_rate).floor() as i64 as u64;
+        2.0 * uniform(index ^ self.seed.rotate_left(32)) - 1.0
+    }
+}
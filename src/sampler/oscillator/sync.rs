@@ -0,0 +1,34 @@
+use super::*;
+
+// Slave oscillator that restarts its cycle every time the master completes
+// one. The discontinuity of each restart is smoothed with a polyBLEP.
+#[derive(Clone)]
+pub struct HardSync {
+    master: f64,
+    slave: f64,
+    waveform: Waveform,
+    sample_rate: f64,
+}
+
+impl HardSync {
+    pub fn new(master: f64, slave: f64, waveform: Waveform, sample_rate: f64) -> DynSampler {
+        Box::new(Self {
+            master,
+            slave,
+            waveform,
+            sample_rate,
+        })
+    }
+}
+
+impl Sampler for HardSync {
+    fn sample(&self, t: f64) -> f64 {
+        let phase = t * self.master;
+        let f = phase - phase.floor();
+        let ratio = self.slave / self.master;
+        let value = self.waveform.at(f * ratio);
+        let jump = self.waveform.at(0.0) - self.waveform.at(ratio);
+        let dt = (self.master / self.sample_rate).min(0.5);
+        value + jump / 2.0 * poly_blep(f, dt)
+    }
+}
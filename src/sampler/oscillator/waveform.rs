@@ -0,0 +1,111 @@
+use super::*;
+use std::cell::RefCell;
+use std::f64::consts::PI;
+
+// Polynomial band-limited step residual, `x` is the phase in cycles and
+// `dt` the phase increment of one sample
+pub(crate) fn poly_blep(x: f64, dt: f64) -> f64 {
+    if x < dt {
+        let x = x / dt;
+        2.0 * x - x * x - 1.0
+    } else if x > 1.0 - dt {
+        let x = (x - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+thread_local! {
+    // Running sums of the held values from cycle 0 up and from cycle -1
+    // down, every held step is added once however far it is integrated
+    static HELD_SUMS: RefCell<(Vec<f64>, Vec<f64>)> = RefCell::new((vec![0.0], vec![0.0]));
+}
+
+// Signed sum of the held values between cycle 0 and `cycles`
+fn held_sum(cycles: i64) -> f64 {
+    let held = |c: i64| 2.0 * uniform(c as u64) - 1.0;
+    HELD_SUMS.with(|sums| {
+        let (up, down) = &mut *sums.borrow_mut();
+        let (sums, n, sign) = if cycles >= 0 {
+            (up, cycles as usize, 1)
+        } else {
+            (down, cycles.unsigned_abs() as usize, -1)
+        };
+        while sums.len() <= n {
+            let c = sums.len() as i64;
+            let value = if sign > 0 { held(c - 1) } else { held(-c) };
+            sums.push(sums[c as usize - 1] + value);
+        }
+        sums[n] * sign as f64
+    })
+}
+
+// Basic shapes as a function of phase in cycles, matching the oscillators
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Sine,
+    Triangle,
+    Square(f64),
+    Sawtooth,
+    // A new random value every cycle
+    SampleAndHold,
+}
+
+impl Waveform {
+    pub fn at(&self, phase: f64) -> f64 {
+        let f = phase - phase.floor();
+        match *self {
+            Waveform::Sine => (2.0 * PI * phase).sin(),
+            Waveform::Triangle => 2.0 * (2.0 * (phase - (phase + 0.5).floor())).abs() - 1.0,
+            Waveform::Square(pulse_width) => {
+                if f < pulse_width {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Sawtooth => f * 2.0 - 1.0,
+            Waveform::SampleAndHold => 2.0 * uniform(phase.floor() as i64 as u64) - 1.0,
+        }
+    }
+    // Integral of `at` from 0 to `phase`
+    pub fn integral(&self, phase: f64) -> f64 {
+        let cycles = phase.floor();
+        let f = phase - cycles;
+        match *self {
+            Waveform::Sine => (1.0 - (2.0 * PI * phase).cos()) / (2.0 * PI),
+            Waveform::Triangle => {
+                if f < 0.5 {
+                    2.0 * f * f - f
+                } else {
+                    -2.0 * f * f + 3.0 * f - 1.0
+                }
+            }
+            Waveform::Square(pulse_width) => {
+                let partial = if f < pulse_width {
+                    f
+                } else {
+                    2.0 * pulse_width - f
+                };
+                cycles * (2.0 * pulse_width - 1.0) + partial
+            }
+            Waveform::Sawtooth => f * f - f,
+            Waveform::SampleAndHold => held_sum(cycles as i64) + f * self.at(phase),
+        }
+    }
+    // `at` with the jumps of the square and sawtooth smoothed by polyBLEPs,
+    // `dt` is the phase increment of one sample
+    pub fn band_limited(&self, phase: f64, dt: f64) -> f64 {
+        let f = phase - phase.floor();
+        let dt = dt.abs().min(0.5);
+        match *self {
+            Waveform::Square(pulse_width) => {
+                let g = f - pulse_width;
+                self.at(phase) + poly_blep(f, dt) - poly_blep(g - g.floor(), dt)
+            }
+            Waveform::Sawtooth => self.at(phase) - poly_blep(f, dt),
+            _ => self.at(phase),
+        }
+    }
+}
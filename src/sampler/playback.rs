@@ -0,0 +1,133 @@
+use super::*;
+use crate::filter::Interpolation;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LoopMode {
+    NoLoop,
+    // Plays the whole sample whatever the gate
+    OneShot,
+    Continuous,
+    // Loops while the gate is held, then plays on past the loop end
+    Sustain,
+}
+
+// Plays shared sample data back at `rate` times its speed
+#[derive(Clone)]
+pub struct Playback {
+    samples: Arc<Vec<f64>>,
+    sample_rate: f64,
+    pub rate: f64,
+    // In samples
+    pub offset: f64,
+    pub loop_mode: LoopMode,
+    // End is exclusive
+    pub loop_points: (usize, usize),
+    pub gate: f64,
+    pub interpolation: Interpolation,
+}
+
+impl Playback {
+    pub fn new(samples: Arc<Vec<f64>>, sample_rate: f64, rate: f64) -> Self {
+        let length = samples.len();
+        Self {
+            samples,
+            sample_rate,
+            rate,
+            offset: 0.0,
+            loop_mode: LoopMode::NoLoop,
+            loop_points: (0, length),
+            gate: f64::INFINITY,
+            interpolation: Interpolation::Cubic,
+        }
+    }
+    pub fn with_loop(mut self, mode: LoopMode, start: usize, end: usize) -> Self {
+        self.loop_mode = mode;
+        self.loop_points = (start.min(end), end.min(self.samples.len()));
+        self
+    }
+    pub fn with_gate(mut self, gate: f64) -> Self {
+        self.gate = gate;
+        self
+    }
+    pub fn with_offset(mut self, offset: f64) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    // Seconds of output, infinite for a continuous loop
+    pub fn length(&self) -> f64 {
+        if self.loop_mode == LoopMode::Continuous && self.looping() {
+            return f64::INFINITY;
+        }
+        let remaining = (self.samples.len() as f64 - self.offset).max(0.0);
+        match self.loop_mode {
+            LoopMode::Sustain if self.looping() => {
+                let past = self.samples.len() as f64 - self.wrap(self.position(self.gate));
+                self.gate + past / (self.sample_rate * self.rate)
+            }
+            _ => remaining / (self.sample_rate * self.rate),
+        }
+    }
+
+    fn looping(&self) -> bool {
+        self.loop_points.1 > self.loop_points.0
+    }
+    fn position(&self, t: f64) -> f64 {
+        self.offset + t * self.sample_rate * self.rate
+    }
+    fn wrap(&self, position: f64) -> f64 {
+        let (start, end) = (self.loop_points.0 as f64, self.loop_points.1 as f64);
+        if position < end {
+            position
+        } else {
+            start + (position - start) % (end - start)
+        }
+    }
+    fn at(&self, index: isize) -> f64 {
+        if index < 0 {
+            return 0.0;
+        }
+        *self.samples.get(index as usize).unwrap_or(&0.0)
+    }
+}
+
+impl Sampler for Playback {
+    fn sample(&self, t: f64) -> f64 {
+        if t < 0.0 {
+            return 0.0;
+        }
+        let position = match self.loop_mode {
+            _ if !self.looping() => self.position(t),
+            LoopMode::Continuous => self.wrap(self.position(t)),
+            LoopMode::Sustain if t < self.gate => self.wrap(self.position(t)),
+            LoopMode::Sustain => {
+                self.wrap(self.position(self.gate)) + self.position(t) - self.position(self.gate)
+            }
+            LoopMode::NoLoop | LoopMode::OneShot => self.position(t),
+        };
+        let whole = position.floor() as isize;
+        let fraction = position - position.floor();
+        // Neighbours inside a loop come from the other end of it
+        let at = |offset: isize| {
+            let index = whole + offset;
+            let (start, end) = (self.loop_points.0 as isize, self.loop_points.1 as isize);
+            let wraps = self.looping()
+                && index >= end
+                && (self.loop_mode == LoopMode::Continuous
+                    || (self.loop_mode == LoopMode::Sustain && t < self.gate));
+            self.at(if wraps { index - end + start } else { index })
+        };
+        match self.interpolation {
+            Interpolation::Nearest => at(fraction.round() as isize),
+            Interpolation::Linear => at(0) + (at(1) - at(0)) * fraction,
+            Interpolation::Cubic => {
+                let (y0, y1, y2, y3) = (at(-1), at(0), at(1), at(2));
+                let c1 = 0.5 * (y2 - y0);
+                let c2 = y0 - 2.5 * y1 + 2.0 * y2 - 0.5 * y3;
+                let c3 = 0.5 * (y3 - y0) + 1.5 * (y1 - y2);
+                ((c3 * fraction + c2) * fraction + c1) * fraction + y1
+            }
+        }
+    }
+}
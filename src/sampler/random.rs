@@ -0,0 +1,13 @@
+// Scrambles the bits of `seed` (the splitmix64 finalizer)
+pub(crate) fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// Stateless pseudo random numbers (splitmix64), the same seed always gives
+// the same value so samplers stay deterministic and cloneable
+pub(crate) fn uniform(seed: u64) -> f64 {
+    (mix(seed) >> 11) as f64 / (1u64 << 53) as f64
+}
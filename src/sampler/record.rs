@@ -23,11 +23,17 @@ impl Record {
         }
     }
     pub fn apply_filter<F: Filter>(&mut self, mut filter: F) {
-        let mut output = Vec::new();
-        for sample in self.samples.iter() {
-            output.push(filter.apply(*sample));
-        }
-        self.samples = output;
+        filter.process(&mut self.samples);
+    }
+    // Keeps rendering the tail of the filter, at most `max_tail` seconds,
+    // and drops its latency so the output lines up with the input
+    pub fn apply_filter_compensated<F: Filter>(&mut self, mut filter: F, max_tail: f64) {
+        let tail = filter.tail().min((max_tail * self.sample_rate) as usize);
+        let latency = filter.latency();
+        self.samples
+            .resize(self.samples.len() + latency + tail, 0.0);
+        filter.process(&mut self.samples);
+        self.samples.drain(..latency);
     }
     pub fn convolve(&mut self, filter_fft: &Vec<Complex<f64>>) {
         let mut padded = self.samples.clone();
@@ -51,6 +57,10 @@ impl Record {
 
 impl Sampler for Record {
     fn sample(&self, t: f64) -> f64 {
+        // Negative times would saturate to the first sample
+        if t < 0.0 {
+            return 0.0;
+        }
         let ind = (t * self.sample_rate) as usize;
         *self.samples.get(ind).unwrap_or(&0f64)
     }
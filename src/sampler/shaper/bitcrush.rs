@@ -0,0 +1,20 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct BitCrush {
+    levels: f64,
+}
+
+impl BitCrush {
+    pub fn new(bits: f64) -> DynSampler {
+        Box::new(Self {
+            levels: 2f64.powf(bits - 1.0),
+        })
+    }
+}
+
+impl Sampler for BitCrush {
+    fn sample(&self, t: f64) -> f64 {
+        (t * self.levels).round() / self.levels
+    }
+}
@@ -0,0 +1,61 @@
+use super::*;
+
+// Transfer curves map an input value to an output value, so `t` is the
+// input sample rather than time. Use them through `Response` or
+// `filter::Waveshaper`.
+
+#[derive(Clone)]
+pub struct Tanh {
+    drive: f64,
+}
+
+impl Tanh {
+    pub fn new(drive: f64) -> DynSampler {
+        Box::new(Self { drive })
+    }
+}
+
+impl Sampler for Tanh {
+    fn sample(&self, t: f64) -> f64 {
+        // The limit of the normalised curve as the drive goes to zero
+        if self.drive.abs() < 1e-9 {
+            return t;
+        }
+        (t * self.drive).tanh() / self.drive.tanh()
+    }
+}
+
+#[derive(Clone)]
+pub struct SoftClip {
+    drive: f64,
+}
+
+impl SoftClip {
+    pub fn new(drive: f64) -> DynSampler {
+        Box::new(Self { drive })
+    }
+}
+
+impl Sampler for SoftClip {
+    fn sample(&self, t: f64) -> f64 {
+        let x = (t * self.drive).clamp(-1.0, 1.0);
+        1.5 * (x - x * x * x / 3.0)
+    }
+}
+
+#[derive(Clone)]
+pub struct HardClip {
+    threshold: f64,
+}
+
+impl HardClip {
+    pub fn new(threshold: f64) -> DynSampler {
+        Box::new(Self { threshold })
+    }
+}
+
+impl Sampler for HardClip {
+    fn sample(&self, t: f64) -> f64 {
+        t.clamp(-self.threshold, self.threshold)
+    }
+}
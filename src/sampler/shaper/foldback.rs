@@ -0,0 +1,25 @@
+use super::*;
+
+#[derive(Clone)]
+pub struct Foldback {
+    threshold: f64,
+}
+
+impl Foldback {
+    pub fn new(threshold: f64) -> DynSampler {
+        Box::new(Self { threshold })
+    }
+}
+
+impl Sampler for Foldback {
+    fn sample(&self, t: f64) -> f64 {
+        // Reflects back and forth between -threshold and threshold
+        let period = 4.0 * self.threshold;
+        let x = (t + self.threshold).rem_euclid(period);
+        if x < 2.0 * self.threshold {
+            x - self.threshold
+        } else {
+            3.0 * self.threshold - x
+        }
+    }
+}
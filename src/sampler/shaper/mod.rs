@@ -0,0 +1,11 @@
+use super::*;
+
+mod bitcrush;
+mod clip;
+mod foldback;
+mod tube;
+
+pub use bitcrush::*;
+pub use clip::*;
+pub use foldback::*;
+pub use tube::*;
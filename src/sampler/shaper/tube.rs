@@ -0,0 +1,30 @@
+use super::*;
+
+// Asymmetric saturation, the bias shifts the operating point so positive
+// and negative half waves clip differently and even harmonics appear.
+#[derive(Clone)]
+pub struct Tube {
+    drive: f64,
+    bias: f64,
+}
+
+impl Tube {
+    pub fn new(drive: f64, bias: f64) -> DynSampler {
+        Box::new(Self { drive, bias })
+    }
+    fn curve(&self, x: f64) -> f64 {
+        if x >= 0.0 {
+            1.0 - (-x).exp()
+        } else {
+            // Softer on the negative side
+            -(1.0 - (0.5 * x).exp()) * 2.0 / 3.0
+        }
+    }
+}
+
+impl Sampler for Tube {
+    fn sample(&self, t: f64) -> f64 {
+        // Remove the DC offset the bias introduces
+        self.curve(t * self.drive + self.bias) - self.curve(self.bias)
+    }
+}
@@ -0,0 +1,110 @@
+use super::*;
+use std::f64::consts::PI;
+
+// How detuned voices are placed within the spread
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    Linear,
+    // Denser towards the center pitch, like the classic supersaw
+    Center,
+    Random,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Unison {
+    pub count: usize,
+    // Distance in cents between the lowest and the highest voice
+    pub spread: f64,
+    pub distribution: Distribution,
+    // How much of a period the start phase of each voice is randomized by
+    pub phase: f64,
+    // Stereo width, 0 keeps all voices centered
+    pub stereo: f64,
+    pub seed: u64,
+}
+
+impl Unison {
+    pub fn new(count: usize, spread: f64) -> Self {
+        Self {
+            count: count.max(1),
+            spread,
+            distribution: Distribution::Linear,
+            phase: 1.0,
+            stereo: 0.0,
+            seed: 0,
+        }
+    }
+
+    // Position of every voice between -1 and 1
+    pub fn offsets(&self) -> Vec<f64> {
+        if self.count == 1 {
+            return vec![0.0];
+        }
+        (0..self.count)
+            .map(|i| {
+                let x = 2.0 * i as f64 / (self.count - 1) as f64 - 1.0;
+                match self.distribution {
+                    Distribution::Linear => x,
+                    Distribution::Center => x * x.abs(),
+                    Distribution::Random => {
+                        2.0 * uniform(self.seed.wrapping_mul(31).wrapping_add(i as u64)) - 1.0
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // Every voice with its pan position between -1 and 1
+    pub fn voices<F>(&self, pitch: f64, creator: F) -> Vec<(f64, DynSampler)>
+    where
+        F: Fn(f64) -> DynSampler,
+    {
+        self.offsets()
+            .into_iter()
+            .enumerate()
+            .map(|(i, offset)| {
+                let freq = pitch * 2f64.powf(offset * self.spread / 2.0 / 1200.0);
+                let phase = self.phase * uniform(self.seed ^ ((i as u64 + 1) << 32));
+                (
+                    offset * self.stereo,
+                    Delay::new(creator(freq), -phase / freq),
+                )
+            })
+            .collect()
+    }
+
+    // Voices are assumed uncorrelated, so the level drops by the square root
+    // of their count to keep the loudness constant
+    pub fn build<F>(&self, pitch: f64, creator: F) -> DynSampler
+    where
+        F: Fn(f64) -> DynSampler,
+    {
+        let level = 1.0 / (self.count as f64).sqrt();
+        Compound::new(
+            self.voices(pitch, creator)
+                .into_iter()
+                .map(|(_, v)| (level, v))
+                .collect(),
+        )
+    }
+
+    // Left and right channels, panned with the constant power law
+    pub fn build_stereo<F>(&self, pitch: f64, creator: F) -> (DynSampler, DynSampler)
+    where
+        F: Fn(f64) -> DynSampler,
+    {
+        let level = 1.0 / (self.count as f64).sqrt();
+        let (left, right) = self
+            .voices(pitch, creator)
+            .into_iter()
+            .map(|(pan, v)| {
+                let angle = (pan + 1.0) * PI / 4.0;
+                (
+                    (level * angle.cos() * 2f64.sqrt(), v.clone()),
+                    (level * angle.sin() * 2f64.sqrt(), v),
+                )
+            })
+            .unzip();
+        (Compound::new(left), Compound::new(right))
+    }
+}
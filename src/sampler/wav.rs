@@ -0,0 +1,241 @@
+use super::*;
+use std::io;
+
+// A decoded WAV file, channels are mixed down to mono
+#[derive(Clone)]
+pub struct Wav {
+    pub record: Record,
+    pub channels: usize,
+    // First loop of the `smpl` chunk, the end is exclusive
+    pub loop_points: Option<(usize, usize)>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+fn u16_at(data: &[u8], at: usize) -> io::Result<u16> {
+    data.get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| invalid("truncated WAV file"))
+}
+
+fn u32_at(data: &[u8], at: usize) -> io::Result<u32> {
+    data.get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| invalid("truncated WAV file"))
+}
+
+impl Wav {
+    // 8, 16, 24 and 32 bit integer or 32 and 64 bit float PCM
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if data.len() < 12 || &data[..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+            return Err(invalid("not a WAV file"));
+        }
+        let mut format = None;
+        let mut samples = None;
+        let mut loop_points = None;
+        let mut at = 12;
+        while at + 8 <= data.len() {
+            let size = u32_at(data, at + 4)? as usize;
+            let body = &data[at + 8..(at + 8 + size).min(data.len())];
+            match &data[at..at + 4] {
+                b"fmt " => {
+                    let mut tag = u16_at(body, 0)?;
+                    // WAVE_FORMAT_EXTENSIBLE keeps the real tag in its GUID
+                    if tag == 0xfffe {
+                        tag = u16_at(body, 24)?;
+                    }
+                    format = Some((
+                        tag,
+                        u16_at(body, 2)? as usize,
+                        u32_at(body, 4)? as f64,
+                        u16_at(body, 14)? as usize,
+                    ));
+                }
+                b"data" => samples = Some(body),
+                b"smpl" if u32_at(body, 28)? > 0 => {
+                    let start = u32_at(body, 36 + 8)? as usize;
+                    let end = u32_at(body, 36 + 12)? as usize + 1;
+                    loop_points = Some((start, end));
+                }
+                _ => {}
+            }
+            // Chunks are padded to an even size
+            at += 8 + size + size % 2;
+        }
+        let (tag, channels, sample_rate, bits) = format.ok_or_else(|| invalid("no fmt chunk"))?;
+        let samples = samples.ok_or_else(|| invalid("no data chunk"))?;
+        if channels == 0 {
+            return Err(invalid("no channels"));
+        }
+        let width = bits / 8;
+        let decode: fn(&[u8]) -> f64 = match (tag, bits) {
+            (1, 8) => |b| (b[0] as f64 - 128.0) / 128.0,
+            (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f64 / 32768.0,
+            (1, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f64 / 2147483648.0,
+            (1, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64 / 2147483648.0,
+            (3, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f64,
+            (3, 64) => |b| f64::from_le_bytes(b.try_into().unwrap()),
+            _ => return Err(invalid("unsupported WAV sample format")),
+        };
+        let samples = samples
+            .chunks_exact(width * channels)
+            .map(|frame| frame.chunks_exact(width).map(decode).sum::<f64>() / channels as f64)
+            .collect();
+        Ok(Self {
+            record: Record {
+                sample_rate,
+                samples,
+            },
+            channels,
+            loop_points,
+        })
+    }
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&std::fs::read(path)?)
+    }
+}
+
+impl Record {
+    pub fn load_wav(path: &str) -> io::Result<Self> {
+        Ok(Wav::load(path)?.record)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) fn chunk(id: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = id.to_vec();
+        chunk.extend((body.len() as u32).to_le_bytes());
+        chunk.extend(body);
+        if body.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    pub(crate) fn fmt(tag: u16, channels: u16, bits: u16) -> Vec<u8> {
+        let width = (bits / 8) as u32 * channels as u32;
+        let mut body = vec![];
+        body.extend(tag.to_le_bytes());
+        body.extend(channels.to_le_bytes());
+        body.extend(44100u32.to_le_bytes());
+        body.extend((44100 * width).to_le_bytes());
+        body.extend((width as u16).to_le_bytes());
+        body.extend(bits.to_le_bytes());
+        body
+    }
+
+    pub(crate) fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut data = b"RIFF".to_vec();
+        data.extend((body.len() as u32 + 4).to_le_bytes());
+        data.extend(b"WAVE");
+        data.extend(body);
+        data
+    }
+
+    fn assert_samples(wav: &Wav, expected: &[f64]) {
+        assert_eq!(wav.record.samples.len(), expected.len());
+        for (a, b) in wav.record.samples.iter().zip(expected) {
+            assert!((a - b).abs() < 1e-6, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn decodes_every_sample_format() {
+        let formats: [(u16, u16, Vec<u8>); 6] = [
+            (1, 8, vec![192, 64, 128]),
+            (
+                1,
+                16,
+                [16384i16, -16384, 0]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+            ),
+            (1, 24, vec![0, 0, 0x40, 0, 0, 0xc0, 0, 0, 0]),
+            (
+                1,
+                32,
+                [1 << 30, -(1i32 << 30), 0]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+            ),
+            (
+                3,
+                32,
+                [0.5f32, -0.5, 0.0]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+            ),
+            (
+                3,
+                64,
+                [0.5f64, -0.5, 0.0]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+            ),
+        ];
+        for (tag, bits, samples) in formats {
+            let wav = Wav::parse(&riff(&[
+                chunk(b"fmt ", &fmt(tag, 1, bits)),
+                chunk(b"data", &samples),
+            ]))
+            .unwrap();
+            assert_eq!(wav.record.sample_rate, 44100.0);
+            assert_samples(&wav, &[0.5, -0.5, 0.0]);
+        }
+    }
+
+    #[test]
+    fn reads_the_tag_of_extensible_formats_and_mixes_down() {
+        let mut body = fmt(0xfffe, 2, 32);
+        body.extend(22u16.to_le_bytes());
+        body.extend(32u16.to_le_bytes());
+        body.extend(3u32.to_le_bytes());
+        // KSDATAFORMAT_SUBTYPE_IEEE_FLOAT
+        body.extend(3u16.to_le_bytes());
+        body.extend([0, 0, 0, 0, 0x10, 0, 0x80, 0, 0, 0xaa, 0, 0x38, 0x9b, 0x71]);
+        let samples: Vec<u8> = [1.0f32, 0.0, -0.5, -0.5]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let wav = Wav::parse(&riff(&[chunk(b"fmt ", &body), chunk(b"data", &samples)])).unwrap();
+        assert_eq!(wav.channels, 2);
+        assert_samples(&wav, &[0.5, -0.5]);
+    }
+
+    #[test]
+    fn reads_the_first_sampler_loop_after_padded_chunks() {
+        let mut smpl = vec![0; 36];
+        smpl[28..32].copy_from_slice(&1u32.to_le_bytes());
+        for value in [0u32, 0, 2, 5, 0, 0] {
+            smpl.extend(value.to_le_bytes());
+        }
+        let wav = Wav::parse(&riff(&[
+            chunk(b"fmt ", &fmt(1, 1, 8)),
+            chunk(b"LIST", &[1, 2, 3]),
+            chunk(b"data", &[128; 8]),
+            chunk(b"smpl", &smpl),
+        ]))
+        .unwrap();
+        assert_eq!(wav.record.samples.len(), 8);
+        assert_eq!(wav.loop_points, Some((2, 6)));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        assert!(Wav::parse(b"RIFF\0\0\0\0AVI ").is_err());
+        let data = riff(&[chunk(b"data", &[0; 4])]);
+        assert!(Wav::parse(&data).is_err());
+        let data = riff(&[chunk(b"fmt ", &fmt(2, 1, 4)), chunk(b"data", &[0; 4])]);
+        assert!(Wav::parse(&data).is_err());
+    }
+}
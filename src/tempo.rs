@@ -0,0 +1,98 @@
+// Note values follow MML, `division` 4 being a quarter note and `tempo` the
+// `t` command
+pub fn note_length(tempo: f64, division: f64, dotted: bool) -> f64 {
+    320.0 / tempo / division * if dotted { 1.5 } else { 1.0 }
+}
+
+// A duration either in seconds or as a note value against a tempo, with the
+// same semantics as the `t` and `l` commands
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Length {
+    Seconds(f64),
+    Note {
+        tempo: f64,
+        division: f64,
+        dotted: bool,
+    },
+}
+
+impl Length {
+    pub fn note(tempo: f64, division: f64, dotted: bool) -> Self {
+        Length::Note {
+            tempo,
+            division,
+            dotted,
+        }
+    }
+    pub fn seconds(&self) -> f64 {
+        match *self {
+            Length::Seconds(s) => s,
+            Length::Note {
+                tempo,
+                division,
+                dotted,
+            } => note_length(tempo, division, dotted),
+        }
+    }
+}
+
+// Tempo changes of a song as (time in seconds, tempo), sorted by time. The
+// first tempo holds from the start of the song.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TempoMap {
+    changes: Vec<(f64, f64)>,
+}
+
+impl TempoMap {
+    pub fn new(tempo: f64) -> Self {
+        Self {
+            changes: vec![(0.0, tempo)],
+        }
+    }
+    pub fn set(&mut self, time: f64, tempo: f64) {
+        let i = self.changes.partition_point(|&(t, _)| t <= time);
+        if i > 0 && self.changes[i - 1].0 == time {
+            self.changes[i - 1].1 = tempo;
+        } else {
+            self.changes.insert(i, (time, tempo));
+        }
+    }
+    pub fn changes(&self) -> &[(f64, f64)] {
+        &self.changes
+    }
+    pub fn tempo_at(&self, time: f64) -> f64 {
+        let i = self.changes.partition_point(|&(t, _)| t <= time);
+        self.changes[i.saturating_sub(1)].1
+    }
+    // A note value locked to the tempo playing at `time`
+    pub fn note(&self, time: f64, division: f64, dotted: bool) -> Length {
+        Length::note(self.tempo_at(time), division, dotted)
+    }
+}
+
+impl Default for TempoMap {
+    fn default() -> Self {
+        Self::new(80.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tempo_changes_hold_until_the_next_one() {
+        let mut map = TempoMap::new(120.0);
+        map.set(4.0, 90.0);
+        map.set(2.0, 100.0);
+        map.set(4.0, 60.0);
+        assert_eq!(map.changes(), &[(0.0, 120.0), (2.0, 100.0), (4.0, 60.0)]);
+        assert_eq!(map.tempo_at(1.0), 120.0);
+        assert_eq!(map.tempo_at(2.0), 100.0);
+        assert_eq!(map.tempo_at(10.0), 60.0);
+        assert_eq!(
+            map.note(3.0, 4.0, false).seconds(),
+            note_length(100.0, 4.0, false)
+        );
+    }
+}